@@ -0,0 +1,84 @@
+//! Benchmarks of parsing large curve files
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rs_gimp_to_icc::{lerp_lookup, parse_curves, transform::compose_curves_in_place};
+use std::hint::black_box;
+
+/// Builds a GIMP curve file with the given amount of samples in each of the value, red, green
+/// and blue channels
+fn synthetic_curve_file(samples: usize) -> String {
+    let values: Vec<String> = (0..samples)
+        .map(|i| (i as f64 / (samples - 1) as f64).powf(0.8).to_string())
+        .collect();
+    let values = values.join(" ");
+
+    let mut text = String::from("# GIMP 'Curves' settings\n\n(time 0)\n(linear no)\n");
+    for channel in ["value", "red", "green", "blue"] {
+        text.push_str(&format!(
+            "(channel {channel})\n(curve\n    (curve-type smooth)\n    (n-samples {samples})\n    (samples {samples} {values}))\n"
+        ));
+    }
+    text
+}
+
+fn parse_benchmark(c: &mut Criterion) {
+    for samples in [256, 4096] {
+        let text = synthetic_curve_file(samples);
+        c.bench_function(&format!("parse_curves {samples} samples"), |b| {
+            b.iter(|| parse_curves(black_box(&text)).unwrap())
+        });
+    }
+}
+
+/// The composition before it was rewritten, which collected a new curve for each channel and
+/// looked each sample up in the gray curve at a float position. Kept as the baseline the
+/// integer [compose_curves_in_place] is compared against
+fn compose_channels_before(gray: &[u16], channels: &[Vec<u16>]) -> Vec<Vec<u16>> {
+    channels
+        .iter()
+        .map(|channel| {
+            channel
+                .iter()
+                .map(|&value| lerp_lookup(gray, value as f32 / u16::MAX as f32))
+                .collect()
+        })
+        .collect()
+}
+
+fn compose_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compose gray over RGB");
+    for samples in [256, 4096] {
+        let gray: Vec<u16> = (0..samples)
+            .map(|i| (i * 65535 / (samples - 1)) as u16)
+            .collect();
+        let inverted: Vec<u16> = gray.iter().rev().copied().collect();
+        let channels = vec![inverted; 3];
+
+        group.bench_with_input(
+            BenchmarkId::new("before", samples),
+            &channels,
+            |b, channels| b.iter(|| compose_channels_before(black_box(&gray), black_box(channels))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("after", samples),
+            &channels,
+            |b, channels| {
+                // the curves are copied outside of the measurement, as parsing already owns them
+                b.iter_batched(
+                    || channels.clone(),
+                    |mut channels| {
+                        for channel in &mut channels {
+                            compose_curves_in_place(black_box(channel), black_box(&gray));
+                        }
+                        channels
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse_benchmark, compose_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+//! Converts a GIMP curve file to an ICC profile with the library API
+//!
+//! Usage: cargo run --example convert -- <curves.txt> [out.icc]
+
+use rs_gimp_to_icc::{build_profile, parse_curves};
+use std::{env, fs, process};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let Some(input) = args.next() else {
+        eprintln!("usage: convert <curves.txt> [out.icc]");
+        process::exit(1);
+    };
+    let output = args.next().unwrap_or_else(|| "out.icc".to_string());
+
+    let text = fs::read_to_string(&input).expect("could not read curve file");
+    let rgb_curves = parse_curves(&text).unwrap_or_else(|err| {
+        eprintln!("could not parse {input}: {err}");
+        process::exit(1);
+    });
+
+    let mut icc = build_profile(&rgb_curves, "Example profile").expect("could not build profile");
+    icc.save_profile_to_file(output.as_ref())
+        .expect("could not save profile");
+    println!("saved {output}");
+}
@@ -0,0 +1,9 @@
+//! Feeds arbitrary bytes to the GIMP curve parser, which must return an error instead of
+//! panicking on malformed input
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = rs_gimp_to_icc::parse_curves(&String::from_utf8_lossy(data));
+});
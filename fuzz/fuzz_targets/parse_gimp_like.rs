@@ -0,0 +1,21 @@
+//! Builds GIMP-like curve files from the fuzzer input, so that mutations reach the sample list
+//! handling instead of stopping at the format detection
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    // each line of the input becomes the contents of one (samples ...) list, optionally with a
+    // channel label taken from the first word
+    let mut file = String::from("# GIMP 'Curves' settings\n\n(time 0)\n(linear no)\n");
+    for line in text.lines() {
+        match line.split_once(':') {
+            Some((channel, samples)) => {
+                file.push_str(&format!("(channel {channel})\n(samples {samples}))\n"))
+            }
+            None => file.push_str(&format!("(samples {line}))\n")),
+        }
+    }
+    let _ = rs_gimp_to_icc::parse_curves(&file);
+});
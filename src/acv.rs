@@ -0,0 +1,197 @@
+//! Parsing of Photoshop .acv curve files
+
+use crate::{compose_channels, CurveChannels, CurveParseError, ParseOptions};
+
+/// Photoshop versions write either version 1 or version 4 files, which share the same layout
+/// for the curves
+const VERSIONS: [u16; 2] = [1, 4];
+
+/// Checks whether file contents look like a Photoshop .acv file by their version and curve count
+pub fn is_acv(bytes: &[u8]) -> bool {
+    bytes.len() >= 4
+        && VERSIONS.contains(&u16::from_be_bytes([bytes[0], bytes[1]]))
+        && u16::from_be_bytes([bytes[2], bytes[3]]) > 0
+}
+
+/// Reads the next big endian u16 from the file contents
+fn read_u16(bytes: &mut &[u8]) -> Result<u16, CurveParseError> {
+    let (value, rest) = bytes
+        .split_first_chunk::<2>()
+        .ok_or(CurveParseError::InvalidBinary(
+            "file ended in the middle of a curve",
+        ))?;
+    *bytes = rest;
+    Ok(u16::from_be_bytes(*value))
+}
+
+/// Parses a Photoshop .acv curve file into 3 colour channel curves of 256 samples, like
+/// [crate::parse_curves]. The file stores the composite RGB curve followed by the red, green and
+/// blue curves as 0-255 control points, which are joined with a natural cubic spline as
+/// Photoshop does. Channels missing from the file are left as identity curves
+pub fn parse_acv(bytes: &[u8]) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    parse_acv_with_options(bytes, &ParseOptions::default())
+}
+
+/// Parses a Photoshop .acv curve file, see [parse_acv]. The composite curve stands in for the
+/// value curve, which [ParseOptions::skip_gray] and [ParseOptions::skip_gray_channels] keep out
+/// of the red, green and blue curves. Photoshop saves one curve per channel and none for
+/// transparency, so there is nothing for [ParseOptions::curve_index] or
+/// [ParseOptions::include_alpha] to pick
+pub fn parse_acv_with_options(
+    bytes: &[u8],
+    options: &ParseOptions,
+) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    let channels = parse_acv_channels(bytes)?;
+    let curves = vec![channels.gray, channels.red, channels.green, channels.blue];
+    Ok(compose_channels(curves, options))
+}
+
+/// Parses the composite, red, green and blue curves of a Photoshop .acv curve file as they are
+/// saved in it, with the composite curve as the gray curve
+pub fn parse_acv_channels(bytes: &[u8]) -> Result<CurveChannels, CurveParseError> {
+    let mut rest = bytes;
+    let version = read_u16(&mut rest)?;
+    if !VERSIONS.contains(&version) {
+        return Err(CurveParseError::InvalidBinary("unknown .acv version"));
+    }
+    let count = read_u16(&mut rest)?;
+    if count == 0 {
+        return Err(CurveParseError::WrongCurveCount(0));
+    }
+
+    // composite, red, green and blue. Any further curves are for other colour modes
+    let mut curves = (0..count.min(4))
+        .map(|_| {
+            let point_count = read_u16(&mut rest)?;
+            let mut points = (0..point_count)
+                .map(|_| {
+                    // points are stored as output, input
+                    let y = read_u16(&mut rest)?;
+                    let x = read_u16(&mut rest)?;
+                    Ok((x.min(255) as f64, y.min(255) as f64))
+                })
+                .collect::<Result<Vec<_>, CurveParseError>>()?;
+            points.sort_by(|a, b| a.0.total_cmp(&b.0));
+            points.dedup_by(|a, b| a.0 == b.0);
+            Ok(spline_points(&points))
+        })
+        .collect::<Result<Vec<Vec<u16>>, CurveParseError>>()?;
+    log::info!("detected Photoshop .acv version {version} format, {count} channels");
+    curves.resize_with(4, || spline_points(&[]));
+
+    Ok(CurveChannels::from_curves(curves, false))
+}
+
+/// Interpolates sorted 0-255 control points to 256 samples scaled from 0 to 65535 with a
+/// natural cubic spline. Values before the first and after the last point are held flat
+pub(crate) fn spline_points(points: &[(f64, f64)]) -> Vec<u16> {
+    let n = points.len();
+    // second derivatives at the points, which are zero at the ends of a natural spline. Solved
+    // from the tridiagonal system with the Thomas algorithm
+    let mut second = vec![0.0; n];
+    if n > 2 {
+        let mut upper = vec![0.0; n];
+        let mut rhs = vec![0.0; n];
+        for i in 1..n - 1 {
+            let (x0, y0) = points[i - 1];
+            let (x1, y1) = points[i];
+            let (x2, y2) = points[i + 1];
+            let (h0, h1) = (x1 - x0, x2 - x1);
+            let diagonal = 2.0 * (h0 + h1) - h0 * upper[i - 1];
+            upper[i] = h1 / diagonal;
+            rhs[i] = (6.0 * ((y2 - y1) / h1 - (y1 - y0) / h0) - h0 * rhs[i - 1]) / diagonal;
+        }
+        for i in (1..n - 1).rev() {
+            second[i] = rhs[i] - upper[i] * second[i + 1];
+        }
+    }
+
+    (0..256)
+        .map(|x| {
+            let x = x as f64;
+            let y = match points.iter().position(|&(px, _)| px >= x) {
+                // no control points at all means an untouched, diagonal curve
+                None if points.is_empty() => x,
+                None => points[n - 1].1,
+                Some(0) => points[0].1,
+                Some(i) => {
+                    let (x0, y0) = points[i - 1];
+                    let (x1, y1) = points[i];
+                    let h = x1 - x0;
+                    let a = (x1 - x) / h;
+                    let b = (x - x0) / h;
+                    a * y0
+                        + b * y1
+                        + ((a * a * a - a) * second[i - 1] + (b * b * b - b) * second[i]) * h * h
+                            / 6.0
+                }
+            };
+            (y.clamp(0.0, 255.0) / 255.0 * u16::MAX as f64).round() as u16
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds .acv file contents from curves of (input, output) control points
+    fn acv_bytes(curves: &[&[(u16, u16)]]) -> Vec<u8> {
+        let mut bytes = vec![0, 4];
+        bytes.extend_from_slice(&(curves.len() as u16).to_be_bytes());
+        for points in curves {
+            bytes.extend_from_slice(&(points.len() as u16).to_be_bytes());
+            for &(x, y) in *points {
+                bytes.extend_from_slice(&y.to_be_bytes());
+                bytes.extend_from_slice(&x.to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn diagonal_acv_is_identity() {
+        let diagonal: &[(u16, u16)] = &[(0, 0), (255, 255)];
+        let bytes = acv_bytes(&[diagonal; 5]);
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+
+        assert!(is_acv(&bytes));
+        assert_eq!(parse_acv(&bytes).unwrap(), vec![ramp; 3]);
+    }
+
+    #[test]
+    fn acv_curve_passes_through_control_points() {
+        let diagonal: &[(u16, u16)] = &[(0, 0), (255, 255)];
+        let brighter: &[(u16, u16)] = &[(0, 0), (64, 96), (255, 255)];
+        let curves = parse_acv(&acv_bytes(&[diagonal, brighter, diagonal, diagonal])).unwrap();
+
+        assert_eq!(curves[0][64], 96 * 257);
+        assert_eq!(curves[0][0], 0);
+        assert_eq!(curves[0][255], 65535);
+        // the spline is smooth, so the curve is above the diagonal on both sides of the point
+        assert!(curves[0][32] > 32 * 257 && curves[0][160] > 160 * 257);
+        assert_eq!(curves[1][128], 128 * 257);
+    }
+
+    #[test]
+    fn truncated_acv_fails() {
+        let mut bytes = acv_bytes(&[&[(0, 0), (255, 255)]]);
+        bytes.pop();
+
+        assert!(matches!(
+            parse_acv(&bytes),
+            Err(CurveParseError::InvalidBinary(_))
+        ));
+        assert!(!is_acv(b"# GIMP Curves File"));
+    }
+
+    #[test]
+    fn acv_fixture_parses() {
+        let bytes = std::fs::read("test/photoshop_curve.acv").unwrap();
+        let curves = parse_acv(&bytes).unwrap();
+
+        assert_eq!(curves.len(), 3);
+        assert_eq!(curves[0][64], 96 * 257);
+        assert_eq!(curves[1][128], 110 * 257);
+    }
+}
@@ -0,0 +1,107 @@
+//! Measurements of what a curve does to the image, for summarizing curves
+
+/// Range of 0-1 input values used for fitting the gamma. The shadows and highlights are left
+/// out, as the ends of a curve are often shaped differently from the midtones
+const MIDTONES: std::ops::RangeInclusive<f64> = 0.1..=0.9;
+
+/// Estimates the gamma of a curve by fitting output = input ^ gamma to its midtones with least
+/// squares in log space. Returns 1.0 if the curve has no usable midtone samples, as with an
+/// empty curve
+pub fn estimate_gamma(curve: &[u16]) -> f32 {
+    let last = curve.len().saturating_sub(1).max(1) as f64;
+    let (mut sum_xy, mut sum_xx) = (0.0, 0.0);
+    for (i, &value) in curve.iter().enumerate() {
+        let x = i as f64 / last;
+        let y = value as f64 / u16::MAX as f64;
+        if MIDTONES.contains(&x) && y > 0.0 {
+            sum_xy += x.ln() * y.ln();
+            sum_xx += x.ln() * x.ln();
+        }
+    }
+
+    if sum_xx == 0.0 {
+        1.0
+    } else {
+        (sum_xy / sum_xx) as f32
+    }
+}
+
+/// How far apart two curves are, in 16-bit values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurveDifference {
+    /// Largest absolute difference between samples at the same position
+    pub max: u16,
+    /// Index of the sample with the largest difference, the first one if several are as large
+    pub max_index: usize,
+    /// Mean absolute difference over all samples
+    pub mean: f64,
+}
+
+/// Compares two curves sample by sample. A curve with a different amount of samples is
+/// resampled to the amount of the first one first
+pub fn compare_curves(a: &[u16], b: &[u16]) -> CurveDifference {
+    let resampled;
+    let b = if a.len() == b.len() {
+        b
+    } else {
+        resampled = crate::transform::resample_curve(b, a.len());
+        &resampled
+    };
+
+    let differences: Vec<u16> = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).collect();
+    let (max_index, max) =
+        differences
+            .iter()
+            .copied()
+            .enumerate()
+            .fold(
+                (0, 0),
+                |best, (i, diff)| if diff > best.1 { (i, diff) } else { best },
+            );
+    let mean = differences.iter().map(|&diff| diff as f64).sum::<f64>() / a.len() as f64;
+    CurveDifference {
+        max,
+        max_index,
+        mean,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_of_power_curve_is_found() {
+        for gamma in [0.5, 1.0, 2.2] {
+            let curve: Vec<u16> = (0..256)
+                .map(|x| ((x as f64 / 255.0).powf(gamma) * 65535.0).round() as u16)
+                .collect();
+            assert!((estimate_gamma(&curve) - gamma as f32).abs() < 0.01);
+        }
+        assert_eq!(estimate_gamma(&[]), 1.0);
+        assert_eq!(estimate_gamma(&[32768]), 1.0);
+    }
+
+    #[test]
+    fn curve_difference_is_measured() {
+        let a: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        let mut b = a.clone();
+        b[10] += 100;
+        b[200] -= 300;
+
+        let difference = compare_curves(&a, &b);
+        assert_eq!(difference.max, 300);
+        assert_eq!(difference.max_index, 200);
+        assert!((difference.mean - 400.0 / 256.0).abs() < 1e-9);
+        assert_eq!(compare_curves(&a, &a).max, 0);
+
+        // a curve with more samples is compared at the positions of the first one
+        let fine: Vec<u16> = (0..512).map(|x| (x * 65535 / 511) as u16).collect();
+        assert!(compare_curves(&a, &fine).max <= 1);
+    }
+
+    #[test]
+    fn flat_black_curve_has_default_gamma() {
+        assert_eq!(estimate_gamma(&[0; 256]), 1.0);
+    }
+}
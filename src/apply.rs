@@ -0,0 +1,103 @@
+//! Applying curves to the pixels of an image, baking their effect into it
+
+use crate::{lerp_lookup, scale_u16_to_u8_range};
+use image::DynamicImage;
+
+/// Builds a lookup table with an entry for every value of a channel with the given maximum
+fn lookup_table<T>(curve: &[u16], max: usize, convert: fn(u16) -> T) -> Vec<T> {
+    (0..=max)
+        .map(|i| convert(lerp_lookup(curve, i as f32 / max as f32)))
+        .collect()
+}
+
+/// Replaces the first 3 channels of each pixel with their entries in the tables, leaving any
+/// other channel such as alpha as it is
+fn apply_tables<T: Copy + Into<usize>>(samples: &mut [T], channels: usize, tables: &[Vec<T>]) {
+    for pixel in samples.chunks_exact_mut(channels) {
+        for (value, table) in pixel.iter_mut().zip(tables) {
+            *value = table[(*value).into()];
+        }
+    }
+}
+
+/// Applies the red, green and blue curves to the colour channels of an image, leaving alpha as
+/// it is. A single curve is applied to all of them. 8-bit images stay 8-bit and other images
+/// are applied to as 16-bit, so that the curves keep their accuracy. Grayscale images are
+/// converted to RGB, as the curves can differ per channel
+pub fn apply_curves(image: &DynamicImage, curves: &[Vec<u16>]) -> DynamicImage {
+    let channels: Vec<&Vec<u16>> = match curves.len() {
+        1 => vec![&curves[0]; 3],
+        _ => curves[..3].iter().collect(),
+    };
+    let eight_bit = matches!(
+        image,
+        DynamicImage::ImageLuma8(_)
+            | DynamicImage::ImageLumaA8(_)
+            | DynamicImage::ImageRgb8(_)
+            | DynamicImage::ImageRgba8(_)
+    );
+    let alpha = image.color().has_alpha();
+
+    if eight_bit {
+        let tables: Vec<Vec<u8>> = channels
+            .iter()
+            .map(|curve| lookup_table(curve, u8::MAX as usize, scale_u16_to_u8_range))
+            .collect();
+        if alpha {
+            let mut rgba = image.to_rgba8();
+            apply_tables(&mut rgba, 4, &tables);
+            DynamicImage::ImageRgba8(rgba)
+        } else {
+            let mut rgb = image.to_rgb8();
+            apply_tables(&mut rgb, 3, &tables);
+            DynamicImage::ImageRgb8(rgb)
+        }
+    } else {
+        let tables: Vec<Vec<u16>> = channels
+            .iter()
+            .map(|curve| lookup_table(curve, u16::MAX as usize, |value| value))
+            .collect();
+        if alpha {
+            let mut rgba = image.to_rgba16();
+            apply_tables(&mut rgba, 4, &tables);
+            DynamicImage::ImageRgba16(rgba)
+        } else {
+            let mut rgb = image.to_rgb16();
+            apply_tables(&mut rgb, 3, &tables);
+            DynamicImage::ImageRgb16(rgb)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb, Rgba};
+
+    fn curves() -> Vec<Vec<u16>> {
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        let inverted: Vec<u16> = ramp.iter().rev().copied().collect();
+        let dark = vec![0; 256];
+        vec![inverted, ramp, dark]
+    }
+
+    #[test]
+    fn curves_are_applied_to_8_bit_pixels() {
+        let image = ImageBuffer::from_fn(2, 1, |x, _| Rgb([x as u8 * 100 + 10, 20, 30]));
+        let applied = apply_curves(&DynamicImage::ImageRgb8(image), &curves()).to_rgb8();
+
+        assert_eq!(applied.get_pixel(0, 0), &Rgb([245, 20, 0]));
+        assert_eq!(applied.get_pixel(1, 0), &Rgb([145, 20, 0]));
+    }
+
+    #[test]
+    fn curves_are_applied_to_16_bit_pixels_keeping_alpha() {
+        let image = ImageBuffer::from_pixel(1, 1, Rgba([1000u16, 30000, 65535, 12345]));
+        let applied = apply_curves(&DynamicImage::ImageRgba16(image), &curves());
+
+        let DynamicImage::ImageRgba16(applied) = applied else {
+            panic!("16-bit image was converted to {:?}", applied.color());
+        };
+        assert_eq!(applied.get_pixel(0, 0), &Rgba([64535, 30000, 0, 12345]));
+    }
+}
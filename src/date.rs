@@ -0,0 +1,233 @@
+//! Setting the creation date in the ICC header, which lcms2 always sets to the current time
+
+use lcms2::Profile;
+use std::{fmt, str::FromStr};
+
+/// Location of the creation date in the ICC header, as 6 big endian 16-bit numbers
+const DATE_OFFSET: usize = 24;
+
+/// A creation date and time in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CreationDate {
+    pub year: u16,
+    pub month: u16,
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Number of days in a month of the proleptic Gregorian calendar
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl CreationDate {
+    /// Converts seconds since the Unix epoch, as used by SOURCE_DATE_EPOCH, to a date. Fails
+    /// for timestamps whose year doesn't fit in the 16-bit year of the ICC header
+    pub fn from_unix_timestamp(timestamp: i64) -> Result<Self, DateParseError> {
+        let days = timestamp.div_euclid(86400);
+        let seconds = timestamp.rem_euclid(86400);
+
+        // inverse of days_from_civil
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        let year = u16::try_from(year).map_err(|_| DateParseError::OutOfRange(timestamp))?;
+
+        Ok(CreationDate {
+            year,
+            month: month as u16,
+            day: day as u16,
+            hour: (seconds / 3600) as u16,
+            minute: (seconds / 60 % 60) as u16,
+            second: (seconds % 60) as u16,
+        })
+    }
+
+    /// Seconds since the Unix epoch
+    pub fn unix_timestamp(&self) -> i64 {
+        days_from_civil(self.year as i64, self.month as i64, self.day as i64) * 86400
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64
+    }
+}
+
+/// Error for dates that can't be written to the ICC header
+#[derive(Debug, PartialEq)]
+pub enum DateParseError {
+    /// The text is not a valid RFC 3339 timestamp
+    Invalid(String),
+    /// The Unix timestamp is in a year before 0 or after 65535, which the header can't hold
+    OutOfRange(i64),
+}
+
+impl fmt::Display for DateParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateParseError::Invalid(text) => write!(
+                f,
+                "{text:?} is not an RFC 3339 date such as 2024-01-31T12:00:00Z"
+            ),
+            DateParseError::OutOfRange(timestamp) => write!(
+                f,
+                "timestamp {timestamp} is outside of the years 0 to 65535 an ICC profile can hold"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DateParseError {}
+
+impl FromStr for CreationDate {
+    type Err = DateParseError;
+
+    /// Parses an RFC 3339 timestamp, e.g. "2024-01-31T12:00:00Z" or
+    /// "2024-01-31T14:00:00.5+02:00". Fractions of seconds are dropped
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || DateParseError::Invalid(s.to_string());
+        let number = |range: std::ops::Range<usize>| -> Result<i64, DateParseError> {
+            let digits = s.get(range).ok_or_else(err)?;
+            if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(err());
+            }
+            digits.parse().map_err(|_| err())
+        };
+
+        // only ASCII can be valid, which also makes slicing by byte safe
+        let separators_valid = s.is_ascii()
+            && s.len() >= 20
+            && &s[4..5] == "-"
+            && &s[7..8] == "-"
+            && matches!(&s[10..11], "T" | "t" | " ")
+            && &s[13..14] == ":"
+            && &s[16..17] == ":";
+        if !separators_valid {
+            return Err(err());
+        }
+
+        let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+        let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+        if !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return Err(err());
+        }
+
+        // skip fractional seconds
+        let rest = s[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+        let offset = match rest {
+            "Z" | "z" => 0,
+            _ if rest.len() == 6 && &rest[3..4] == ":" => {
+                let sign = match &rest[..1] {
+                    "+" => 1,
+                    "-" => -1,
+                    _ => return Err(err()),
+                };
+                let start = s.len() - 6;
+                sign * (number(start + 1..start + 3)? * 3600 + number(start + 4..start + 6)? * 60)
+            }
+            _ => return Err(err()),
+        };
+
+        let local = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+        CreationDate::from_unix_timestamp(local - offset).map_err(|_| err())
+    }
+}
+
+/// Returns a copy of the profile with the given creation date in its header
+pub fn with_creation_date(profile: &Profile, date: CreationDate) -> Result<Profile, lcms2::Error> {
+    let mut icc = profile.icc()?;
+    let fields = [
+        date.year,
+        date.month,
+        date.day,
+        date.hour,
+        date.minute,
+        date.second,
+    ];
+    for (i, field) in fields.iter().enumerate() {
+        let at = DATE_OFFSET + i * 2;
+        icc[at..at + 2].copy_from_slice(&field.to_be_bytes());
+    }
+    Profile::new_icc(&icc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc3339_dates_are_parsed_to_utc() {
+        let expected = CreationDate {
+            year: 2024,
+            month: 2,
+            day: 29,
+            hour: 23,
+            minute: 30,
+            second: 5,
+        };
+        assert_eq!("2024-02-29T23:30:05Z".parse(), Ok(expected));
+        assert_eq!("2024-03-01T01:30:05.25+02:00".parse(), Ok(expected));
+        assert!("2024-02-29".parse::<CreationDate>().is_err());
+        assert!("2024-13-01T00:00:00Z".parse::<CreationDate>().is_err());
+    }
+
+    #[test]
+    fn invalid_days_and_non_ascii_text_are_rejected() {
+        assert!("2023-02-28T00:00:00Z".parse::<CreationDate>().is_ok());
+        assert!("2023-02-29T00:00:00Z".parse::<CreationDate>().is_err());
+        assert!("2024-02-31T12:00:00Z".parse::<CreationDate>().is_err());
+        assert!("2024-04-31T12:00:00Z".parse::<CreationDate>().is_err());
+        assert!("2000-02-29T00:00:00Z".parse::<CreationDate>().is_ok());
+        assert!("1900-02-29T00:00:00Z".parse::<CreationDate>().is_err());
+        assert!("202é-01-31T12:00:00Z".parse::<CreationDate>().is_err());
+        assert!("2024-01-31T12:00:00+0é:00".parse::<CreationDate>().is_err());
+    }
+
+    #[test]
+    fn unix_timestamps_round_trip() {
+        let date = CreationDate::from_unix_timestamp(1_700_000_000).unwrap();
+        assert_eq!(date, "2023-11-14T22:13:20Z".parse().unwrap());
+        assert_eq!(date.unix_timestamp(), 1_700_000_000);
+        assert_eq!(
+            CreationDate::from_unix_timestamp(0),
+            "1970-01-01T00:00:00Z".parse()
+        );
+        assert_eq!(
+            CreationDate::from_unix_timestamp(i64::MAX / 2),
+            Err(DateParseError::OutOfRange(i64::MAX / 2))
+        );
+        assert!(CreationDate::from_unix_timestamp(-100_000_000_000).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Parsing of the tone curve module in darktable .dtstyle style files
+
+use crate::{acv::spline_points, compose_channels, CurveChannels, CurveParseError, ParseOptions};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::ZlibDecoder;
+use regex::Regex;
+use std::io::Read;
+
+/// Most nodes a tone curve module curve can have
+const MAX_NODES: usize = 20;
+/// Offset of the node counts of the L, a and b curves in the module parameters, after the
+/// nodes of all 3 curves as pairs of f32
+const NODE_COUNTS: usize = 3 * MAX_NODES * 8;
+/// Offset of the interpolation types of the L, a and b curves, after their node counts
+const CURVE_TYPES: usize = NODE_COUNTS + 3 * 4;
+/// Offset of the scaling mode of the a and b curves, after the node counts and curve types
+const AUTOSCALE: usize = CURVE_TYPES + 3 * 4;
+/// Curve type of a natural cubic spline, the only one that is interpolated the same way here
+const CUBIC_SPLINE: i32 = 0;
+/// Scaling mode where the L curve is applied to each RGB channel
+const AUTOSCALE_RGB: i32 = 3;
+/// Earliest tone curve module version with the current layout of the curves
+const MIN_VERSION: u32 = 4;
+
+/// Checks whether file contents look like a darktable style
+pub fn is_dtstyle(bytes: &[u8]) -> bool {
+    String::from_utf8_lossy(&bytes[..bytes.len().min(512)]).contains("<darktable_style")
+}
+
+/// Reads the contents of a `<name>...</name>` element of a style plugin
+fn field<'a>(plugin: &'a str, name: &str) -> Option<&'a str> {
+    let start = plugin.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + plugin[start..].find(&format!("</{name}>"))?;
+    Some(plugin[start..end].trim())
+}
+
+/// Decodes module parameters, which darktable saves either as hex or, when they are large
+/// enough, zlib compressed and base64 encoded after a "gz" prefix and a 2 digit compression
+/// factor
+fn decode_params(text: &str) -> Result<Vec<u8>, CurveParseError> {
+    match text.strip_prefix("gz") {
+        Some(rest) => {
+            let encoded = rest.get(2..).unwrap_or_default();
+            let compressed = STANDARD
+                .decode(encoded)
+                .map_err(|_| CurveParseError::InvalidBinary("module parameters are not base64"))?;
+            let mut params = Vec::new();
+            ZlibDecoder::new(&compressed[..])
+                .read_to_end(&mut params)
+                .map_err(|_| {
+                    CurveParseError::InvalidBinary("module parameters could not be decompressed")
+                })?;
+            Ok(params)
+        }
+        None => (0..text.len())
+            .step_by(2)
+            .map(|i| {
+                text.get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or(CurveParseError::InvalidBinary(
+                        "module parameters are not hex",
+                    ))
+            })
+            .collect(),
+    }
+}
+
+fn read_i32(params: &[u8], at: usize) -> i32 {
+    i32::from_le_bytes(params[at..at + 4].try_into().unwrap())
+}
+
+fn read_f32(params: &[u8], at: usize) -> f32 {
+    f32::from_le_bytes(params[at..at + 4].try_into().unwrap())
+}
+
+/// Parses the tone curve of a darktable style into 3 colour channel curves of 256 samples, like
+/// [crate::parse_curves]. See [parse_dtstyle_channels] for how the curve is read. The L curve
+/// is applied to the colour channels unless [ParseOptions::skip_gray] or
+/// [ParseOptions::skip_gray_channels] says otherwise. Only the first enabled tone curve module
+/// is read whatever [ParseOptions::curve_index] is, and [ParseOptions::include_alpha] has no
+/// effect as the module works on colour alone
+pub fn parse_dtstyle_with_options(
+    bytes: &[u8],
+    options: &ParseOptions,
+) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    let channels = parse_dtstyle_channels(bytes)?;
+    let curves = vec![channels.gray, channels.red, channels.green, channels.blue];
+    Ok(compose_channels(curves, options))
+}
+
+/// Parses the L curve of the first enabled tone curve module in a darktable style as the gray
+/// curve, leaving the colour channels as identity curves. The control points are joined with a
+/// natural cubic spline, which is darktable's default curve type, and a warning is logged for
+/// curves of other types. The a and b curves have no equivalent in a gamma table and are
+/// ignored
+pub fn parse_dtstyle_channels(bytes: &[u8]) -> Result<CurveChannels, CurveParseError> {
+    let text = String::from_utf8_lossy(bytes);
+    let plugins = Regex::new(r"(?s)<plugin>(.*?)</plugin>").unwrap();
+    let plugin = plugins
+        .captures_iter(&text)
+        .map(|caps| caps.get(1).unwrap().as_str())
+        .find(|plugin| {
+            field(plugin, "operation") == Some("tonecurve") && field(plugin, "enabled") != Some("0")
+        })
+        .ok_or(CurveParseError::InvalidBinary(
+            "style has no enabled tone curve module",
+        ))?;
+
+    let version = field(plugin, "module").and_then(|version| version.parse::<u32>().ok());
+    if version.is_some_and(|version| version < MIN_VERSION) {
+        return Err(CurveParseError::InvalidBinary(
+            "tone curve module version is too old",
+        ));
+    }
+    let params = decode_params(field(plugin, "op_params").unwrap_or_default())?;
+    if params.len() < AUTOSCALE + 4 {
+        return Err(CurveParseError::InvalidBinary(
+            "tone curve module parameters are too short",
+        ));
+    }
+
+    let node_count = read_i32(&params, NODE_COUNTS);
+    if !(1..=MAX_NODES as i32).contains(&node_count) {
+        return Err(CurveParseError::InvalidBinary(
+            "tone curve has an invalid amount of nodes",
+        ));
+    }
+    let mut points: Vec<(f64, f64)> = (0..node_count as usize)
+        .map(|i| {
+            let x = read_f32(&params, i * 8).clamp(0.0, 1.0);
+            let y = read_f32(&params, i * 8 + 4).clamp(0.0, 1.0);
+            (x as f64 * 255.0, y as f64 * 255.0)
+        })
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    points.dedup_by(|a, b| a.0 == b.0);
+
+    log::info!("detected darktable style format, tone curve with {node_count} nodes");
+    let curve_type = read_i32(&params, CURVE_TYPES);
+    if curve_type != CUBIC_SPLINE {
+        log::warn!(
+            "the tone curve has interpolation type {curve_type} in darktable, its nodes are joined with a cubic spline instead"
+        );
+    }
+    if read_i32(&params, AUTOSCALE) != AUTOSCALE_RGB {
+        log::warn!(
+            "the tone curve is applied to Lab lightness in darktable, its L curve is applied to each RGB channel instead"
+        );
+    }
+
+    let identity = spline_points(&[]);
+    Ok(CurveChannels::from_curves(
+        vec![
+            spline_points(&points),
+            identity.clone(),
+            identity.clone(),
+            identity,
+        ],
+        false,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dtstyle_fixture_parses() {
+        let bytes = std::fs::read("test/darktable_curve.dtstyle").unwrap();
+        assert!(is_dtstyle(&bytes));
+
+        let channels = parse_dtstyle_channels(&bytes).unwrap();
+        assert_eq!(channels.gray.len(), 256);
+        assert_eq!(channels.gray[0], 0);
+        assert_eq!(channels.gray[255], 65535);
+        // the node at (0.25, 0.35) lifts the shadows
+        assert!(channels.gray[64] > 64 * 257);
+        assert_eq!(channels.red, spline_points(&[]));
+
+        let curves = parse_dtstyle_with_options(&bytes, &ParseOptions::default()).unwrap();
+        assert_eq!(curves.len(), 3);
+        assert_eq!(curves[0], channels.gray);
+    }
+
+    #[test]
+    fn hex_params_decode() {
+        assert_eq!(decode_params("00ff10").unwrap(), vec![0, 255, 16]);
+        assert!(decode_params("0g").is_err());
+    }
+
+    #[test]
+    fn style_without_tone_curve_fails() {
+        let style = b"<darktable_style><style><plugin><operation>exposure</operation></plugin></style></darktable_style>";
+        assert!(matches!(
+            parse_dtstyle_channels(style),
+            Err(CurveParseError::InvalidBinary(_))
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+//! Exporting parsed curves to formats other than ICC profiles
+
+use crate::{lerp_lookup, CurveChannels, CHANNEL_NAMES};
+
+/// Formats curves as CSV with one row per sample and a column per channel, e.g.
+/// "index,r,g,b". The header row is optional, and is all there is without curves
+pub fn curves_to_csv(curves: &[Vec<u16>], header: bool) -> String {
+    let mut csv = String::new();
+    if header {
+        let mut columns = vec!["index".to_string()];
+        columns.extend(
+            CHANNEL_NAMES[..curves.len()]
+                .iter()
+                .map(|name| name.to_lowercase()),
+        );
+        csv.push_str(&format!("{}\n", columns.join(",")));
+    }
+
+    for i in 0..curves.first().map_or(0, Vec::len) {
+        let values: Vec<String> = curves.iter().map(|curve| curve[i].to_string()).collect();
+        csv.push_str(&format!("{i},{}\n", values.join(",")));
+    }
+    csv
+}
+
+/// Formats the red, green and blue curves as a .cube 3D LUT with `size` points along each axis,
+/// applying each curve to its own channel. Inputs between the curve samples are interpolated
+pub fn curves_to_cube(curves: &[Vec<u16>], size: usize, title: &str) -> String {
+    let mut cube = format!(
+        "TITLE \"{}\"\nLUT_3D_SIZE {size}\n",
+        title.replace('"', "'")
+    );
+    cube.push_str("DOMAIN_MIN 0.0 0.0 0.0\nDOMAIN_MAX 1.0 1.0 1.0\n");
+
+    // each channel only depends on its own input, so the outputs are computed once per point
+    let last = (size - 1) as f32;
+    let axes: Vec<Vec<f32>> = curves[..3]
+        .iter()
+        .map(|curve| {
+            (0..size)
+                .map(|i| lerp_lookup(curve, i as f32 / last) as f32 / u16::MAX as f32)
+                .collect()
+        })
+        .collect();
+    // red changes fastest, then green, then blue
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                cube.push_str(&format!(
+                    "{:.6} {:.6} {:.6}\n",
+                    axes[0][r], axes[1][g], axes[2][b]
+                ));
+            }
+        }
+    }
+    cube
+}
+
+/// Formats curves as a JSON object with "gray", "r", "g", "b" and "alpha" arrays and a "linear"
+/// flag. Alpha is null when the file has no alpha curve
+pub fn channels_to_json(channels: &CurveChannels) -> String {
+    serde_json::json!({
+        "gray": channels.gray,
+        "r": channels.red,
+        "g": channels.green,
+        "b": channels.blue,
+        "alpha": channels.alpha,
+        "linear": channels.linear,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_has_a_row_per_sample() {
+        let curves = vec![vec![0, 65535], vec![1, 2], vec![3, 4]];
+
+        assert_eq!(
+            curves_to_csv(&curves, true),
+            "index,r,g,b\n0,0,1,3\n1,65535,2,4\n"
+        );
+        assert_eq!(curves_to_csv(&curves, false), "0,0,1,3\n1,65535,2,4\n");
+        assert_eq!(curves_to_csv(&[], true), "index\n");
+    }
+
+    #[test]
+    fn json_has_all_channels() {
+        let channels = CurveChannels {
+            gray: vec![0, 65535],
+            red: vec![1, 2],
+            green: vec![3, 4],
+            blue: vec![5, 6],
+            alpha: None,
+            linear: false,
+        };
+
+        assert_eq!(
+            channels_to_json(&channels),
+            r#"{"alpha":null,"b":[5,6],"g":[3,4],"gray":[0,65535],"linear":false,"r":[1,2]}"#
+        );
+    }
+
+    #[test]
+    fn cube_applies_each_curve_to_its_channel() {
+        let curves = vec![vec![0, 65535], vec![65535, 0], vec![0, 0]];
+        let cube = curves_to_cube(&curves, 2, "test");
+        let lines: Vec<&str> = cube.lines().collect();
+
+        assert_eq!(lines[0], "TITLE \"test\"");
+        assert_eq!(lines[1], "LUT_3D_SIZE 2");
+        assert_eq!(lines.len(), 4 + 8);
+        assert_eq!(lines[4], "0.000000 1.000000 0.000000");
+        // red changes first
+        assert_eq!(lines[5], "1.000000 1.000000 0.000000");
+        assert_eq!(lines[11], "1.000000 0.000000 0.000000");
+    }
+}
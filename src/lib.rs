@@ -0,0 +1,2249 @@
+//! Conversion of GIMP curve files to ICC profiles with an embedded VCGT gamma table
+
+use lcms2::{Locale, Profile, Tag, ToneCurve, MLU};
+use regex::Regex;
+use std::{fmt, io::Read, path::Path};
+
+pub mod acv;
+pub mod analysis;
+#[cfg(feature = "apply-image")]
+pub mod apply;
+pub mod date;
+pub mod dtstyle;
+pub mod export;
+#[cfg(feature = "palette-preview")]
+pub mod preview;
+pub mod private_tag;
+pub mod raw;
+pub mod transform;
+pub mod validate;
+
+/// Errors that can occur while parsing a GIMP curve file
+#[derive(Debug, PartialEq)]
+pub enum CurveParseError {
+    /// Fewer than the 4 required curves (value, red, green, blue) were found, or more than the 5
+    /// that a plain curve file can have
+    WrongCurveCount(usize),
+    /// A curve did not have the expected amount of samples
+    BadSampleLength { expected: usize, found: usize },
+    /// The amount of values in a (samples N ...) list is not the declared N, which means that the
+    /// file is truncated or corrupted
+    SampleCountMismatch { declared: usize, found: usize },
+    /// The (samples N ...) list of the named channel has no values, as in a truncated file
+    EmptyCurve(&'static str),
+    /// A sample value could not be parsed as a number. The line of the file it is on, counting
+    /// from 1, and its channel are given when they are known
+    NumberParse {
+        token: String,
+        line: Option<usize>,
+        channel: Option<&'static str>,
+    },
+    /// The curve is saved in linear light and [ParseOptions::linear_fail] is set
+    LinearLight,
+    /// A curve file with channel labels has no curve for the named channel
+    MissingChannel(&'static str),
+    /// A curve block past the last one of the file was requested with
+    /// [ParseOptions::curve_index]
+    CurveIndex { index: usize, blocks: usize },
+    /// A file with several curve blocks but no channel labels has an amount of sample lists
+    /// that can't be split into blocks of 4 curves
+    IncompleteBlocks(usize),
+    /// A binary curve file such as a Photoshop .acv file, or the encoded parameters of a
+    /// darktable style, is malformed
+    InvalidBinary(&'static str),
+}
+
+impl fmt::Display for CurveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurveParseError::WrongCurveCount(found) => {
+                write!(
+                    f,
+                    "expected 4 curves in file and optionally an alpha curve, found {found}"
+                )
+            }
+            CurveParseError::BadSampleLength { expected, found } => {
+                write!(f, "expected {expected} samples in curve, found {found}")
+            }
+            CurveParseError::SampleCountMismatch { declared, found } => {
+                write!(f, "curve declares {declared} samples but lists {found}")
+            }
+            CurveParseError::EmptyCurve(name) => {
+                write!(f, "the samples of the {name} channel are empty")
+            }
+            CurveParseError::NumberParse {
+                token,
+                line,
+                channel,
+            } => {
+                write!(f, "could not parse sample value {token:?} as a number")?;
+                if let Some(line) = line {
+                    write!(f, " on line {line}")?;
+                }
+                if let Some(channel) = channel {
+                    write!(f, " in the {channel} channel")?;
+                }
+                Ok(())
+            }
+            CurveParseError::LinearLight => {
+                write!(f, "curve is saved in linear light, which is not supported")
+            }
+            CurveParseError::MissingChannel(name) => {
+                write!(f, "curve file has no {name} channel")
+            }
+            CurveParseError::CurveIndex { index, blocks } => {
+                write!(f, "curve block {index} requested but the file has {blocks} curve blocks, counting from 0")
+            }
+            CurveParseError::IncompleteBlocks(found) => write!(
+                f,
+                "found {found} curves, which can't be split into blocks of 4 curves"
+            ),
+            CurveParseError::InvalidBinary(reason) => write!(f, "invalid curve file: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for CurveParseError {}
+
+impl CurveParseError {
+    /// A [CurveParseError::NumberParse] for a token whose position isn't known yet
+    fn number(token: &str) -> Self {
+        CurveParseError::NumberParse {
+            token: token.to_string(),
+            line: None,
+            channel: None,
+        }
+    }
+
+    /// Adds the line and channel a [CurveParseError::NumberParse] happened at, leaving other
+    /// errors as they are
+    fn at(self, line: usize, channel: &'static str) -> Self {
+        match self {
+            CurveParseError::NumberParse { token, .. } => CurveParseError::NumberParse {
+                token,
+                line: Some(line),
+                channel: Some(channel),
+            },
+            other => other,
+        }
+    }
+}
+
+/// Errors that can occur while building a profile
+#[derive(Debug, PartialEq)]
+pub enum ProfileError {
+    /// The curves can't be made into a profile, as with fewer than the 3 colour curves or a
+    /// curve with fewer than 2 samples
+    Curves(CurveParseError),
+    /// lcms2 could not create the profile, e.g. from an invalid base profile, white point or
+    /// primaries
+    Lcms(lcms2::Error),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Curves(err) => write!(f, "{err}"),
+            ProfileError::Lcms(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<CurveParseError> for ProfileError {
+    fn from(err: CurveParseError) -> Self {
+        ProfileError::Curves(err)
+    }
+}
+
+impl From<lcms2::Error> for ProfileError {
+    fn from(err: lcms2::Error) -> Self {
+        ProfileError::Lcms(err)
+    }
+}
+
+/// Checks that a curve has enough samples to be tabulated
+fn check_curve_length(curve: &[u16]) -> Result<(), CurveParseError> {
+    if curve.len() < 2 {
+        return Err(CurveParseError::BadSampleLength {
+            expected: 2,
+            found: curve.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Names of the curves returned by [parse_curves_with_options], in order
+pub const CHANNEL_NAMES: [&str; 4] = ["R", "G", "B", "A"];
+
+/// Options controlling how curve files are parsed
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// Return an error for curves saved in linear light instead of converting them to sRGB
+    pub linear_fail: bool,
+    /// Include the alpha curve as a 4th curve in the result when the file has one
+    pub include_alpha: bool,
+    /// Return the red, green and blue curves as they are instead of applying the value (gray)
+    /// curve after each of them
+    pub skip_gray: bool,
+    /// Whether the value curve is left out of each of the red, green and blue curves, like
+    /// [ParseOptions::skip_gray] for only some of them
+    pub skip_gray_channels: [bool; 3],
+    /// Which block of curves to use, counting from 0, in GIMP curve files that hold several
+    /// complete sets of curves
+    pub curve_index: usize,
+    /// Drop samples past the declared sample count of a curve when they are all 1, which GIMP
+    /// sometimes pads curves with, instead of failing with
+    /// [CurveParseError::SampleCountMismatch]
+    pub truncate_trailing_padding: bool,
+}
+
+/// Parses e.g. "0.0 0.001 0.033 ..." to vec of numbers scaled from 0 to 65535. Values can be
+/// separated by any whitespace, and values outside of the 0-1 range are clamped to it. NaN and
+/// infinite values are a [CurveParseError::NumberParse] error
+pub fn parse_u16_curve_vec(input: &str) -> Result<Vec<u16>, CurveParseError> {
+    let values = parse_f32_list(input)?;
+
+    let clamped = values.iter().filter(|f| !(0.0..=1.0).contains(*f)).count();
+    if clamped > 0 {
+        log::warn!("{clamped} samples were outside of the 0-1 range and have been clamped");
+    }
+
+    Ok(values
+        .iter()
+        .map(|f| (f.clamp(0.0, 1.0) * (u16::MAX) as f32).round() as u16)
+        .collect())
+}
+
+/// Parses whitespace separated sample values as they are, rejecting NaN and infinite values
+fn parse_f32_list(input: &str) -> Result<Vec<f32>, CurveParseError> {
+    input
+        .split_whitespace()
+        .map(|it| {
+            it.parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| CurveParseError::number(it))
+        })
+        .collect()
+}
+
+/// Scales from 0-65535 to 0-255
+pub fn scale_u16_to_u8_range(input: u16) -> u8 {
+    ((input as f32 / u16::MAX as f32) * u8::MAX as f32).round() as u8
+}
+
+/// Scales a 0-65535 value down to an index into a curve with `len` samples, so that 65535 maps
+/// to the last sample
+pub fn scale_index(value: u16, len: usize) -> usize {
+    // exact integer math, which is also cheaper than dividing floats for every sample
+    (value as u64 * len.saturating_sub(1) as u64 / u16::MAX as u64) as usize
+}
+
+/// Header line of the curve files saved by GIMP versions before 2.10
+const LEGACY_HEADER: &str = "# GIMP Curves File";
+
+/// Parses GIMP's old (pre-2.10) curve format, which stores each channel as a single line of 17
+/// "x y" control point pairs in the 0-255 range, with unused points set to -1. Returns the
+/// value, red, green, blue and possibly alpha curves, linearly interpolated between the control
+/// points to 256 samples each
+pub fn parse_legacy_curves(text: &str) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    let lines: Vec<(usize, &str)> = text
+        .lines()
+        .map(|line| line.trim())
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    // value, red, green, blue and alpha lines
+    if lines.len() < 4 {
+        return Err(CurveParseError::WrongCurveCount(lines.len()));
+    }
+
+    lines[..lines.len().min(5)]
+        .iter()
+        .zip(SAMPLE_CHANNELS)
+        .map(|(&(index, line), name)| {
+            let coords = line
+                .split_whitespace()
+                .map(|it| {
+                    it.parse::<i32>()
+                        .map_err(|_| CurveParseError::number(it).at(index + 1, name))
+                })
+                .collect::<Result<Vec<i32>, _>>()?;
+
+            if coords.len() != 34 {
+                return Err(CurveParseError::BadSampleLength {
+                    expected: 34,
+                    found: coords.len(),
+                });
+            }
+
+            let mut points: Vec<(i32, i32)> = coords
+                .chunks(2)
+                .map(|pair| (pair[0], pair[1]))
+                .filter(|&(x, _)| x >= 0)
+                .map(|(x, y)| (x.min(255), y.clamp(0, 255)))
+                .collect();
+            points.sort_unstable();
+
+            Ok(interpolate_points(&points))
+        })
+        .collect()
+}
+
+/// Linearly interpolates 0-255 control points to 256 samples scaled from 0 to 65535. Values
+/// before the first and after the last point are held flat, as GIMP does
+fn interpolate_points(points: &[(i32, i32)]) -> Vec<u16> {
+    (0..256)
+        .map(|x| {
+            let y = match points.iter().position(|&(px, _)| px >= x) {
+                // no control points at all means an untouched, diagonal curve
+                None if points.is_empty() => x as f32,
+                None => points[points.len() - 1].1 as f32,
+                Some(0) => points[0].1 as f32,
+                Some(i) => {
+                    let (x0, y0) = points[i - 1];
+                    let (x1, y1) = points[i];
+                    y0 as f32 + (y1 - y0) as f32 * (x - x0) as f32 / (x1 - x0) as f32
+                }
+            };
+            (y / u8::MAX as f32 * u16::MAX as f32).round() as u16
+        })
+        .collect()
+}
+
+/// Channel labels of GIMP's new curve format in the order the curves are returned in, alpha
+/// being optional
+pub const SAMPLE_CHANNELS: [&str; 5] = ["value", "red", "green", "blue", "alpha"];
+
+/// Extracts the declared count, values and line of the value, red, green, blue and possibly
+/// alpha sample lists from GIMP's new curve format which is formatted in a LISP-like way. The
+/// curves are picked by their (channel ...) labels, or by their order in files without labels.
+///
+/// Files may hold several blocks of curves, of which the one at `curve_index` is used. With
+/// labels each value curve starts a new block when there are several, and without them more
+/// than 5 curves are split into blocks of 4. With `gray_only` only the value curve is returned,
+/// and the colour curves don't need to be in the file
+fn sample_lists(
+    text: &str,
+    curve_index: usize,
+    gray_only: bool,
+) -> Result<Vec<(usize, &str, usize)>, CurveParseError> {
+    let entries = sample_entries(text);
+
+    let labelled = entries.iter().any(|&(name, _, _, _)| name.is_some());
+    let value_curves = entries
+        .iter()
+        .filter(|&&(name, _, _, _)| name == Some(SAMPLE_CHANNELS[0]))
+        .count();
+    let blocks: Vec<&[_]> = if labelled && value_curves <= 1 {
+        vec![&entries[..]]
+    } else if labelled {
+        entries
+            .chunk_by(|_, &(name, _, _, _)| name != Some(SAMPLE_CHANNELS[0]))
+            .collect()
+    } else if entries.len() <= 5 {
+        vec![&entries[..]]
+    } else if entries.len().is_multiple_of(4) {
+        entries.chunks(4).collect()
+    } else {
+        return Err(CurveParseError::IncompleteBlocks(entries.len()));
+    };
+    let entries = match blocks.get(curve_index) {
+        Some(block) => *block,
+        None if curve_index == 0 => &[][..],
+        None => {
+            return Err(CurveParseError::CurveIndex {
+                index: curve_index,
+                blocks: blocks.len(),
+            })
+        }
+    };
+
+    if blocks.len() > 1 {
+        log::info!(
+            "file has {} curve blocks, using block {curve_index}",
+            blocks.len()
+        );
+    }
+
+    let caps: Vec<(&str, &str, usize)> = if labelled {
+        let find = |wanted: &str| {
+            entries
+                .iter()
+                .find(|&&(name, _, _, _)| name == Some(wanted))
+                .map(|&(_, count, list, line)| (count, list, line))
+        };
+        // 1 value curve (gray), and 3 colour curves (R, G, B). Possibly also alpha
+        let required = if gray_only { 1 } else { 4 };
+        let mut caps = SAMPLE_CHANNELS[..required]
+            .iter()
+            .map(|&name| find(name).ok_or(CurveParseError::MissingChannel(name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !gray_only {
+            caps.extend(find(SAMPLE_CHANNELS[4]));
+        }
+        caps
+    } else {
+        let (required, most) = if gray_only { (1, 1) } else { (4, 5) };
+        if entries.len() < required {
+            return Err(CurveParseError::WrongCurveCount(entries.len()));
+        }
+        entries[..entries.len().min(most)]
+            .iter()
+            .map(|&(_, count, list, line)| (count, list, line))
+            .collect()
+    };
+
+    let caps = caps
+        .into_iter()
+        .zip(SAMPLE_CHANNELS)
+        .map(|((count, list, line), name)| {
+            // the regex only matches digits, but the count may still be too large
+            let count = count
+                .parse()
+                .map_err(|_| CurveParseError::number(count).at(line, name))?;
+            Ok((count, list, line))
+        })
+        .collect::<Result<Vec<(usize, &str, usize)>, CurveParseError>>()?;
+
+    Ok(caps)
+}
+
+/// Channel label, declared count, values and line of a sample list
+type SampleEntry<'a> = (Option<&'a str>, &'a str, &'a str, usize);
+
+/// Finds the sample lists of a GIMP curve file in the new format, along with the channel label
+/// before each of them if there is one
+fn sample_entries(text: &str) -> Vec<SampleEntry<'_>> {
+    // mR flags: multi-line and CRLF mode
+    let re = Regex::new(
+        r"(?Rm)^[ \t]*(?:\(channel[ \t]+(\w+)\)[ \t]*$|\(samples[ \t]+(\d+)([^()\r\n]*)\))",
+    )
+    .unwrap();
+    // gets us the channel label and the declared count and the values portion of
+    // (samples n value1 value2 value3...) in the file. The values end at the closing paren of
+    // the list, so whatever closes the enclosing lists or follows on the line is left out. The
+    // line of each list is counted along the way for pointing at values that don't parse
+    let mut channel = None;
+    let mut entries: Vec<SampleEntry> = Vec::new();
+    let (mut line, mut counted) = (1, 0);
+    for it in re.captures_iter(text) {
+        let start = it.get(0).unwrap().start();
+        line += text[counted..start].matches('\n').count();
+        counted = start;
+        match it.get(1) {
+            Some(name) => channel = Some(name.as_str()),
+            None => entries.push((
+                channel.take(),
+                it.get(2).unwrap().as_str(),
+                it.get(3).unwrap().as_str(),
+                line,
+            )),
+        }
+    }
+    entries
+}
+
+/// Whether the sample lists of a GIMP curve file in the new format are only a value curve, as
+/// saved for a grayscale image: a labelled value curve without red, green and blue curves, or
+/// a single list without labels
+fn has_only_value_curve(text: &str) -> bool {
+    let entries = sample_entries(text);
+    if entries.iter().all(|&(name, _, _, _)| name.is_none()) {
+        return entries.len() == 1;
+    }
+    let has = |wanted: &str| entries.iter().any(|&(name, _, _, _)| name == Some(wanted));
+    has(SAMPLE_CHANNELS[0]) && !SAMPLE_CHANNELS[1..4].iter().any(|&name| has(name))
+}
+
+/// Whether a curve file only has a value curve, as GIMP saves for grayscale images, so that it
+/// can only be made into a grayscale profile with [parse_gray_curve]. Only GIMP curve files in
+/// the new format can be like this
+pub fn is_gray_only(path: &Path, input: &[u8], format: Option<InputFormat>) -> bool {
+    if format.unwrap_or_else(|| InputFormat::detect(path, input)) != InputFormat::Gimp {
+        return false;
+    }
+    let text = String::from_utf8_lossy(input);
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    !text.starts_with(LEGACY_HEADER) && has_only_value_curve(text)
+}
+
+/// Parses the value, red, green, blue and possibly alpha sample lists of GIMP's new curve
+/// format, see [sample_lists]. Curves saved with different amounts of samples are resampled to
+/// the largest amount. See [ParseOptions::truncate_trailing_padding] for `truncate_padding`
+fn parse_sample_curves(
+    text: &str,
+    curve_index: usize,
+    truncate_padding: bool,
+    gray_only: bool,
+) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    let curves = sample_lists(text, curve_index, gray_only)?
+        .into_iter()
+        .zip(SAMPLE_CHANNELS)
+        .map(|((declared, list, line), name)| {
+            if list.trim().is_empty() {
+                return Err(CurveParseError::EmptyCurve(name));
+            }
+            let mut curve = parse_u16_curve_vec(list).map_err(|err| err.at(line, name))?;
+            if truncate_padding
+                && curve.len() > declared
+                && curve[declared..].iter().all(|&value| value == u16::MAX)
+            {
+                log::warn!(
+                    "dropped {} trailing samples of 1 past the declared {declared} samples",
+                    curve.len() - declared
+                );
+                curve.truncate(declared);
+            }
+            if curve.len() != declared {
+                return Err(CurveParseError::SampleCountMismatch {
+                    declared,
+                    found: curve.len(),
+                });
+            }
+            // a single sample has no range to interpolate over
+            if curve.len() < 2 {
+                return Err(CurveParseError::BadSampleLength {
+                    expected: 2,
+                    found: curve.len(),
+                });
+            }
+            Ok(curve)
+        })
+        .collect::<Result<Vec<Vec<u16>>, CurveParseError>>()?;
+    Ok(resample_to_common_length(curves))
+}
+
+/// Resamples curves with differing amounts of samples, such as from a hand edited file, to the
+/// largest amount among them so that they can be handled like curves saved together
+fn resample_to_common_length(curves: Vec<Vec<u16>>) -> Vec<Vec<u16>> {
+    let longest = curves.iter().map(Vec::len).max().unwrap_or_default();
+    if curves.iter().all(|curve| curve.len() == longest) {
+        return curves;
+    }
+    log::info!("curves have different amounts of samples, resampling them all to {longest}");
+    curves
+        .iter()
+        .map(|curve| transform::resample_curve(curve, longest))
+        .collect()
+}
+
+/// Parses the value, red, green, blue and possibly alpha sample lists of a curve file in GIMP's
+/// new format as they are saved, without clamping them to the 0-1 range. The curves are in the
+/// order of [SAMPLE_CHANNELS], taken from the block at `curve_index` like with
+/// [ParseOptions::curve_index]
+pub fn parse_raw_samples(text: &str, curve_index: usize) -> Result<Vec<Vec<f32>>, CurveParseError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    sample_lists(text, curve_index, false)?
+        .into_iter()
+        .zip(SAMPLE_CHANNELS)
+        .map(|((_, list, line), name)| parse_f32_list(list).map_err(|err| err.at(line, name)))
+        .collect()
+}
+
+/// Parses a GIMP curve file into 3 colour channel curves with the default options. Both the new
+/// LISP-like format and the old pre-2.10 format are supported
+pub fn parse_curves(text: &str) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    parse_curves_with_options(text, &ParseOptions::default())
+}
+
+/// Parses a GIMP curve file into 3 colour channel curves, see [parse_curves]. With
+/// [ParseOptions::include_alpha] the unmodified alpha curve follows as a 4th curve if present
+pub fn parse_curves_with_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    // gimp seems to be able to save linear curves which would look wrong applied as they are
+    let linear = text.contains("linear yes");
+    if linear && options.linear_fail {
+        return Err(CurveParseError::LinearLight);
+    }
+
+    let channels = read_channels(text, options.curve_index, options.truncate_trailing_padding)?;
+    let mut curves = vec![channels.gray, channels.red, channels.green, channels.blue];
+    curves.extend(channels.alpha);
+
+    if channels.linear {
+        log::warn!("Curve input is saved in linear light. Converting it to sRGB");
+        // alpha is not a colour and is left as it is
+        for curve in &mut curves[..4] {
+            *curve = transform::delinearize_curve(curve);
+        }
+    }
+
+    // apply gray curve to the RGB curves, reducing 4 curves to 3 colour channel curves
+    let mut result = compose_channels(curves, options);
+
+    if !options.include_alpha {
+        result.truncate(3);
+    }
+
+    Ok(result)
+}
+
+/// Curves of a curve file as they are saved in it, before the value curve is applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurveChannels {
+    /// The value (gray) curve, applied after each of the colour curves
+    pub gray: Vec<u16>,
+    pub red: Vec<u16>,
+    pub green: Vec<u16>,
+    pub blue: Vec<u16>,
+    pub alpha: Option<Vec<u16>>,
+    /// Whether the curves are saved in linear light instead of sRGB
+    pub linear: bool,
+}
+
+impl CurveChannels {
+    /// Takes the value, red, green, blue and optional alpha curves in that order
+    pub(crate) fn from_curves(mut curves: Vec<Vec<u16>>, linear: bool) -> CurveChannels {
+        let alpha = curves.get(4).cloned();
+        curves.truncate(4);
+        let [gray, red, green, blue]: [Vec<u16>; 4] = curves.try_into().unwrap();
+        CurveChannels {
+            gray,
+            red,
+            green,
+            blue,
+            alpha,
+            linear,
+        }
+    }
+}
+
+/// Parses the curves of a GIMP curve file as they are saved in it. Both the new LISP-like format
+/// and the old pre-2.10 format are supported
+pub fn parse_channels(text: &str) -> Result<CurveChannels, CurveParseError> {
+    parse_channels_at(text, 0)
+}
+
+/// Parses the curves of the block at `curve_index` of a GIMP curve file with several blocks of
+/// curves as they are saved in it, see [ParseOptions::curve_index]. Files in the pre-2.10 format
+/// only have one block
+pub fn parse_channels_at(text: &str, curve_index: usize) -> Result<CurveChannels, CurveParseError> {
+    read_channels(text, curve_index, false)
+}
+
+/// [parse_channels_at], with trailing padding dropped like with
+/// [ParseOptions::truncate_trailing_padding] when `truncate_padding` is set
+fn read_channels(
+    text: &str,
+    curve_index: usize,
+    truncate_padding: bool,
+) -> Result<CurveChannels, CurveParseError> {
+    let (curves, linear) = read_curves(text, curve_index, truncate_padding, false)?;
+    Ok(CurveChannels::from_curves(curves, linear))
+}
+
+/// Reads the curves of a GIMP curve file as they are saved in it, and whether they are saved
+/// in linear light. With `gray_only` only the value curve is read, see [sample_lists]
+fn read_curves(
+    text: &str,
+    curve_index: usize,
+    truncate_padding: bool,
+    gray_only: bool,
+) -> Result<(Vec<Vec<u16>>, bool), CurveParseError> {
+    // GIMP on Windows may start the file with a byte order mark
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    // gimp seems to be able to save linear curves which would look wrong applied as they are
+    let linear = text.contains("linear yes");
+    let legacy = text.starts_with(LEGACY_HEADER) || !text.contains("(samples");
+    let curves = if !legacy {
+        parse_sample_curves(text, curve_index, truncate_padding, gray_only)?
+    } else if curve_index == 0 {
+        let mut curves = parse_legacy_curves(text)?;
+        if gray_only {
+            curves.truncate(1);
+        }
+        curves
+    } else {
+        return Err(CurveParseError::CurveIndex {
+            index: curve_index,
+            blocks: 1,
+        });
+    };
+    log::info!(
+        "detected {} format, {} samples, {} channels, linear={}",
+        if legacy {
+            "GIMP pre-2.10 control point"
+        } else {
+            "GIMP 2.10 samples"
+        },
+        curves[0].len(),
+        curves.len(),
+        if linear { "yes" } else { "no" }
+    );
+
+    Ok((curves, linear))
+}
+
+/// Removes the value curve from the value, red, green and blue curves, applying it after each
+/// colour curve unless [ParseOptions::skip_gray] or [ParseOptions::skip_gray_channels] is set.
+/// Curves after blue are returned as they are
+pub(crate) fn compose_channels(mut curves: Vec<Vec<u16>>, options: &ParseOptions) -> Vec<Vec<u16>> {
+    let gray = curves.remove(0);
+    if !options.skip_gray {
+        for (channel, skip) in curves[..3].iter_mut().zip(options.skip_gray_channels) {
+            if !skip {
+                transform::compose_curves_in_place(channel, &gray);
+            }
+        }
+    }
+    curves
+}
+
+/// Reads a table at a 0-1 position by linearly interpolating between the neighbouring entries,
+/// so that 0 maps to the first and 1 to the last entry regardless of the table's length
+///
+/// # Panics
+///
+/// If the table is empty, as there is no value to read. Parsed curves always have at least 2
+/// samples
+pub fn lerp_lookup(table: &[u16], position: f32) -> u16 {
+    let index = position.clamp(0.0, 1.0) * (table.len() - 1) as f32;
+    let low = index.floor() as usize;
+    let high = (low + 1).min(table.len() - 1);
+    let fraction = index - low as f32;
+    (table[low] as f32 * (1.0 - fraction) + table[high] as f32 * fraction).round() as u16
+}
+
+/// Applies the value (gray) master curve after a colour channel curve, so that each output is
+/// gray(channel(x)). See [transform::compose_curves], which this is with the arguments swapped
+pub fn compose_gray_over_channel(gray: &[u16], channel: &[u16]) -> Vec<u16> {
+    transform::compose_curves(channel, gray)
+}
+
+/// Format of a curve input file
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// GIMP curve file, in either the new or the pre-2.10 format
+    Gimp,
+    /// Photoshop .acv curve file
+    Acv,
+    /// darktable .dtstyle style with a tone curve module
+    Dtstyle,
+    /// Plain text with the value, red, green and blue curves on one line each as space
+    /// separated 0-1 values. Never detected automatically
+    Raw,
+}
+
+impl InputFormat {
+    /// Guesses the format from a file extension, returning None for unknown extensions. The
+    /// extension before a .gz extension is used for compressed files
+    pub fn from_path(path: &Path) -> Option<InputFormat> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "gz" => InputFormat::from_path(Path::new(path.file_stem()?)),
+            "acv" => Some(InputFormat::Acv),
+            "txt" => Some(InputFormat::Gimp),
+            "dtstyle" => Some(InputFormat::Dtstyle),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the extension of the path, or from the contents when the
+    /// extension is unknown. Defaults to a GIMP curve file
+    pub fn detect(path: &Path, input: &[u8]) -> InputFormat {
+        InputFormat::from_path(path).unwrap_or(if acv::is_acv(input) {
+            InputFormat::Acv
+        } else if dtstyle::is_dtstyle(input) {
+            InputFormat::Dtstyle
+        } else {
+            InputFormat::Gimp
+        })
+    }
+}
+
+/// Decompresses gzip compressed file contents, recognised by the gzip magic bytes, and returns
+/// other contents as they are
+pub fn decompress_gzip(input: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    if !input.starts_with(&[0x1f, 0x8b]) {
+        return Ok(input);
+    }
+    log::info!("decompressing gzip compressed input");
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(&input[..]).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Parses the curves of a curve file as they are saved in it, detecting the format like
+/// [detect_and_parse]. `curve_index` picks the block of curves in GIMP curve files, see
+/// [ParseOptions::curve_index]
+pub fn detect_and_parse_channels(
+    path: &Path,
+    input: &[u8],
+    format: Option<InputFormat>,
+    curve_index: usize,
+) -> Result<CurveChannels, CurveParseError> {
+    match format.unwrap_or_else(|| InputFormat::detect(path, input)) {
+        InputFormat::Gimp => parse_channels_at(&String::from_utf8_lossy(input), curve_index),
+        InputFormat::Acv => acv::parse_acv_channels(input),
+        InputFormat::Dtstyle => dtstyle::parse_dtstyle_channels(input),
+        InputFormat::Raw => raw::parse_raw_channels(&String::from_utf8_lossy(input)),
+    }
+}
+
+/// Parses the value (gray) curve of a curve file for [build_grayscale_profile], converting it
+/// to sRGB if it is saved in linear light. GIMP curve files may have only the value curve, see
+/// [is_gray_only]. Of the options, only [ParseOptions::linear_fail], [ParseOptions::curve_index]
+/// and [ParseOptions::truncate_trailing_padding] have an effect
+pub fn parse_gray_curve(
+    path: &Path,
+    input: &[u8],
+    format: Option<InputFormat>,
+    options: &ParseOptions,
+) -> Result<Vec<u16>, CurveParseError> {
+    let (gray, linear) = match format.unwrap_or_else(|| InputFormat::detect(path, input)) {
+        InputFormat::Gimp => {
+            let text = String::from_utf8_lossy(input);
+            let (mut curves, linear) = read_curves(
+                &text,
+                options.curve_index,
+                options.truncate_trailing_padding,
+                true,
+            )?;
+            (curves.remove(0), linear)
+        }
+        format => {
+            let channels =
+                detect_and_parse_channels(path, input, Some(format), options.curve_index)?;
+            (channels.gray, channels.linear)
+        }
+    };
+    if !linear {
+        return Ok(gray);
+    }
+    if options.linear_fail {
+        return Err(CurveParseError::LinearLight);
+    }
+    log::warn!("Curve input is saved in linear light. Converting it to sRGB");
+    Ok(transform::delinearize_curve(&gray))
+}
+
+/// Parses curve file contents into 3 colour channel curves, and possibly an alpha curve as with
+/// [parse_curves_with_options]. The format is detected with [InputFormat::detect] unless one is
+/// given
+pub fn detect_and_parse(
+    path: &Path,
+    input: &[u8],
+    format: Option<InputFormat>,
+    options: &ParseOptions,
+) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    match format.unwrap_or_else(|| InputFormat::detect(path, input)) {
+        InputFormat::Gimp => parse_curves_with_options(&String::from_utf8_lossy(input), options),
+        InputFormat::Acv => acv::parse_acv_with_options(input, options),
+        InputFormat::Dtstyle => dtstyle::parse_dtstyle_with_options(input, options),
+        InputFormat::Raw => raw::parse_raw_with_options(&String::from_utf8_lossy(input), options),
+    }
+}
+
+/// Where the curves are written in the generated profile
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum CurveMode {
+    /// Video card gamma table, loaded onto the GPU and applied to the whole display
+    #[default]
+    Vcgt,
+    /// Red, green and blue tone reproduction curves, honored by colour managed applications
+    Trc,
+    /// Both the video card gamma table and the tone reproduction curves. Colour managed
+    /// applications on a display with the gamma table loaded apply the curves twice
+    Both,
+}
+
+/// ICC specification version of the generated profile
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum IccVersion {
+    /// Version 2.1, for older colour pipelines. Text tags are saved as the v2 text and
+    /// textDescription types instead of multi-localized unicode, and tone curves are always
+    /// saved as tables instead of parametric curves
+    #[value(name = "2")]
+    V2,
+    /// Version 4.4, which lcms2 creates by default
+    #[value(name = "4")]
+    V4,
+}
+
+impl IccVersion {
+    fn number(self) -> f64 {
+        match self {
+            IccVersion::V2 => 2.1,
+            IccVersion::V4 => 4.4,
+        }
+    }
+}
+
+/// Rendering intent in the profile header, which colour managed applications may use to pick
+/// the transform between profiles
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum RenderingIntent {
+    /// Perceptual, which keeps the overall look when mapping between gamuts
+    Perceptual,
+    /// Relative colorimetric, which keeps in-gamut colours exact relative to the white point
+    Relative,
+    /// Saturation, which favours vivid colours over accuracy
+    Saturation,
+    /// Absolute colorimetric, which keeps in-gamut colours exact including the white point
+    Absolute,
+}
+
+impl RenderingIntent {
+    fn intent(self) -> lcms2::Intent {
+        match self {
+            RenderingIntent::Perceptual => lcms2::Intent::Perceptual,
+            RenderingIntent::Relative => lcms2::Intent::RelativeColorimetric,
+            RenderingIntent::Saturation => lcms2::Intent::Saturation,
+            RenderingIntent::Absolute => lcms2::Intent::AbsoluteColorimetric,
+        }
+    }
+}
+
+/// Precision of the curve values written to the profile
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum BitDepth {
+    /// Values rounded to the 256 levels of 8-bit tables, for older tools that read the curves
+    /// as 8-bit entries and expect each value to be an exact 8-bit level. lcms2 still saves the
+    /// entries as 16-bit numbers
+    #[value(name = "8")]
+    Eight,
+    /// Full 16-bit values
+    #[default]
+    #[value(name = "16")]
+    Sixteen,
+}
+
+/// Options controlling how the profile is built
+#[derive(Debug, Clone)]
+pub struct ProfileOptions {
+    /// Description or name that will appear in Windows' colour management menu
+    pub description: String,
+    /// Which tags the curves are written to
+    pub mode: CurveMode,
+    /// Rendering intent in the header, the one of the created or base profile is kept when absent
+    pub intent: Option<RenderingIntent>,
+    /// Precision of the tabulated curves. Curves built with [build_gamma_profile] are not
+    /// tabulated and keep their full precision
+    pub bit_depth: BitDepth,
+    /// Copyright notice, the default one of lcms2 is kept when absent
+    pub copyright: Option<String>,
+    /// Device manufacturer description
+    pub manufacturer: Option<String>,
+    /// Device model description
+    pub model: Option<String>,
+    /// Creation date in the header, the current time is used when absent
+    pub creation_date: Option<date::CreationDate>,
+    /// ICC version of the profile, the lcms2 default is used when absent. The VCGT tag is not
+    /// part of the ICC specification and is saved the same way in both versions
+    pub version: Option<IccVersion>,
+    /// Serialized profile that the curves and text tags are written onto instead of a new sRGB
+    /// profile, keeping its primaries and white point. Must be a valid ICC profile
+    pub base_profile: Option<Vec<u8>>,
+    /// Language and country of the text tags, the neutral locale by default
+    pub locale: Locale,
+    /// Translations of the description added next to it
+    pub localized_descriptions: Vec<(Locale, String)>,
+    /// Text describing the curve file the profile was made from, such as its name and a hash of
+    /// its contents. Stored in the private [private_tag::SOURCE_TAG] tag, which doesn't affect
+    /// how the profile is applied
+    pub source: Option<String>,
+    /// Applies the curves on top of the VCGT tag of the base profile instead of replacing it,
+    /// for adjusting a calibrated display. Has no effect without a base profile that has one
+    pub merge_vcgt: bool,
+    /// CIE xy chromaticity of the white point of a new profile, D65 by default. Ignored with a
+    /// base profile, which keeps its own
+    pub white_point: Option<(f64, f64)>,
+    /// CIE xy chromaticities of the red, green and blue primaries of a new RGB profile, written
+    /// as its colorant tags. The sRGB primaries are used by default, and a base profile keeps
+    /// its own
+    pub primaries: Option<[(f64, f64); 3]>,
+    /// Bit depth of the panel the curves are meant for, e.g. 10 for a 10-bit display. Stored
+    /// in the private [private_tag::TARGET_BITS_TAG] tag as a hint, the curves are not changed
+    pub target_bits: Option<u8>,
+}
+
+/// CIE xy chromaticity of the D65 white point of sRGB
+pub const D65: (f64, f64) = (0.3127, 0.3290);
+/// CIE xy chromaticity of the D50 white point of the ICC profile connection space
+pub const D50: (f64, f64) = (0.3457, 0.3585);
+/// CIE xy chromaticities of the red, green and blue primaries of sRGB
+pub const SRGB_PRIMARIES: [(f64, f64); 3] = [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)];
+
+fn xyy((x, y): (f64, f64)) -> lcms2::CIExyY {
+    lcms2::CIExyY { x, y, Y: 1.0 }
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        ProfileOptions {
+            description: "Custom gamma ICC profile".to_string(),
+            mode: CurveMode::default(),
+            intent: None,
+            bit_depth: BitDepth::default(),
+            copyright: None,
+            manufacturer: None,
+            model: None,
+            creation_date: None,
+            version: None,
+            base_profile: None,
+            locale: Locale::none(),
+            localized_descriptions: Vec::new(),
+            source: None,
+            merge_vcgt: false,
+            white_point: None,
+            primaries: None,
+            target_bits: None,
+        }
+    }
+}
+
+/// Replaces a text tag of the profile with an MLU entry for each of the translations
+fn write_text_tag(icc: &mut Profile, signature: lcms2::TagSignature, texts: &[(Locale, &str)]) {
+    icc.remove_tag(signature);
+
+    let mut mlu = MLU::new(texts.len());
+    for &(locale, text) in texts {
+        mlu.set_text(text, locale);
+    }
+    icc.write_tag(signature, Tag::MLU(&mlu));
+}
+
+/// Builds a tone curve from the samples, rounded to 8-bit levels for [BitDepth::Eight]
+fn tabulated_curve(curve: &[u16], bit_depth: BitDepth) -> ToneCurve {
+    match bit_depth {
+        BitDepth::Eight => {
+            let levels: Vec<u16> = curve
+                .iter()
+                .map(|&value| scale_u16_to_u8_range(value) as u16 * 257)
+                .collect();
+            ToneCurve::new_tabulated(&levels)
+        }
+        BitDepth::Sixteen => ToneCurve::new_tabulated(curve),
+    }
+}
+
+/// Builds an sRGB profile with the given RGB curves embedded as a VCGT tag
+pub fn build_profile(rgb_curves: &[Vec<u16>], description: &str) -> Result<Profile, ProfileError> {
+    build_profile_with_options(
+        rgb_curves,
+        &ProfileOptions {
+            description: description.to_string(),
+            ..Default::default()
+        },
+    )
+}
+
+/// Builds an sRGB profile with the given RGB curves, see [build_profile]. A 4th alpha curve is
+/// stored in the private [private_tag::ALPHA_CURVE_TAG] tag. Fails with
+/// [CurveParseError::WrongCurveCount] for fewer than 3 curves, or if lcms2 can't read
+/// [ProfileOptions::base_profile] or create a profile with its white point and primaries
+pub fn build_profile_with_options(
+    rgb_curves: &[Vec<u16>],
+    options: &ProfileOptions,
+) -> Result<Profile, ProfileError> {
+    if rgb_curves.len() < 3 {
+        return Err(CurveParseError::WrongCurveCount(rgb_curves.len()).into());
+    }
+    for curve in rgb_curves {
+        check_curve_length(curve)?;
+    }
+    let r_tc = tabulated_curve(&rgb_curves[0], options.bit_depth);
+    let g_tc = tabulated_curve(&rgb_curves[1], options.bit_depth);
+    let b_tc = tabulated_curve(&rgb_curves[2], options.bit_depth);
+
+    let icc = build_profile_from_tone_curves([&r_tc, &g_tc, &b_tc], options)?;
+
+    match rgb_curves.get(3) {
+        Some(alpha) => {
+            let data: Vec<u8> = alpha.iter().flat_map(|value| value.to_be_bytes()).collect();
+            Ok(private_tag::with_private_tag(
+                &icc,
+                private_tag::ALPHA_CURVE_TAG,
+                &data,
+            )?)
+        }
+        None => Ok(icc),
+    }
+}
+
+/// Builds an sRGB profile with the same power function curve, output = input ^ gamma, on each
+/// channel instead of curves parsed from a file
+pub fn build_gamma_profile(gamma: f64, options: &ProfileOptions) -> Result<Profile, ProfileError> {
+    let curve = ToneCurve::new(gamma);
+    Ok(build_profile_from_tone_curves(
+        [&curve, &curve, &curve],
+        options,
+    )?)
+}
+
+/// Builds a grayscale profile with the white point of the options, D65 by default, and the gray
+/// curve as its only tone reproduction curve, instead of an sRGB profile. Grayscale profiles
+/// have no VCGT tag, so [ProfileOptions::mode] has no effect and the curve only applies in
+/// colour managed applications. Fails if lcms2 can't create a profile with the white point
+pub fn build_grayscale_profile(
+    gray: &[u16],
+    options: &ProfileOptions,
+) -> Result<Profile, ProfileError> {
+    check_curve_length(gray)?;
+    let curve = tabulated_curve(gray, options.bit_depth);
+    let white_point = xyy(options.white_point.unwrap_or(D65));
+    let mut icc = Profile::new_gray(&white_point, &curve)?;
+    write_common_tags(&mut icc, options);
+
+    Ok(finish_profile(icc, options)?)
+}
+
+/// Adds the source and target bit depth tags and creation date of the options, which are written to the serialized
+/// profile after lcms2 has created its tags
+fn finish_profile(icc: Profile, options: &ProfileOptions) -> Result<Profile, lcms2::Error> {
+    let icc = match &options.source {
+        Some(source) => {
+            private_tag::with_private_tag(&icc, private_tag::SOURCE_TAG, source.as_bytes())?
+        }
+        None => icc,
+    };
+    let icc = match options.target_bits {
+        Some(bits) => private_tag::with_private_tag(&icc, private_tag::TARGET_BITS_TAG, &[bits])?,
+        None => icc,
+    };
+    match options.creation_date {
+        Some(creation_date) => date::with_creation_date(&icc, creation_date),
+        None => Ok(icc),
+    }
+}
+
+/// Sets the version and writes the text tags of the options to a profile
+fn write_common_tags(icc: &mut Profile, options: &ProfileOptions) {
+    // lcms2 decides the tag types based on the version when saving
+    if let Some(version) = options.version {
+        icc.set_version(version.number());
+    }
+    if let Some(intent) = options.intent {
+        icc.set_header_rendering_intent(intent.intent());
+    }
+
+    // description that is shown in Windows colour management
+    let mut descriptions = vec![(options.locale, options.description.as_str())];
+    descriptions.extend(
+        options
+            .localized_descriptions
+            .iter()
+            .map(|(locale, text)| (*locale, text.as_str())),
+    );
+    write_text_tag(
+        icc,
+        lcms2::TagSignature::ProfileDescriptionTag,
+        &descriptions,
+    );
+
+    let text_tags = [
+        (lcms2::TagSignature::CopyrightTag, &options.copyright),
+        (lcms2::TagSignature::DeviceMfgDescTag, &options.manufacturer),
+        (lcms2::TagSignature::DeviceModelDescTag, &options.model),
+    ];
+    for (signature, text) in text_tags {
+        if let Some(text) = text {
+            write_text_tag(icc, signature, &[(options.locale, text)]);
+        }
+    }
+}
+
+fn build_profile_from_tone_curves(
+    [r_tc, g_tc, b_tc]: [&lcms2::ToneCurveRef; 3],
+    options: &ProfileOptions,
+) -> Result<Profile, lcms2::Error> {
+    let mut icc = match &options.base_profile {
+        Some(base) => Profile::new_icc(base)?,
+        None if options.white_point.is_none() && options.primaries.is_none() => Profile::new_srgb(),
+        None => new_rgb_profile(
+            options.white_point.unwrap_or(D65),
+            options.primaries.unwrap_or(SRGB_PRIMARIES),
+        )?,
+    };
+    write_common_tags(&mut icc, options);
+
+    if matches!(options.mode, CurveMode::Vcgt | CurveMode::Both) {
+        let tc_refs: [&lcms2::ToneCurveRef; 3] = [r_tc, g_tc, b_tc];
+        match read_vcgt_curves(&icc).filter(|_| options.merge_vcgt) {
+            Some(base_curves) => {
+                log::info!("applying the curves on top of the VCGT tag of the base profile");
+                // each base entry is looked up in our curve, so ours applies after the base
+                let merged: Vec<ToneCurve> = base_curves
+                    .iter()
+                    .zip(tc_refs)
+                    .map(|(base, tc)| {
+                        let entries = transform::compose_curves(base, tc.estimated_entries());
+                        ToneCurve::new_tabulated(&entries)
+                    })
+                    .collect();
+                let merged_refs: [&lcms2::ToneCurveRef; 3] = [&merged[0], &merged[1], &merged[2]];
+                icc.write_tag(lcms2::TagSignature::VcgtTag, Tag::VcgtCurves(merged_refs));
+            }
+            None => {
+                icc.write_tag(lcms2::TagSignature::VcgtTag, Tag::VcgtCurves(tc_refs));
+            }
+        }
+    }
+    if matches!(options.mode, CurveMode::Trc | CurveMode::Both) {
+        // replaces the sRGB transfer functions of the base profile
+        icc.write_tag(lcms2::TagSignature::RedTRCTag, Tag::ToneCurve(r_tc));
+        icc.write_tag(lcms2::TagSignature::GreenTRCTag, Tag::ToneCurve(g_tc));
+        icc.write_tag(lcms2::TagSignature::BlueTRCTag, Tag::ToneCurve(b_tc));
+    }
+
+    finish_profile(icc, options)
+}
+
+/// Builds an RGB profile with the given white point and primaries and the sRGB transfer
+/// function. lcms2 stores the white point as a chromatic adaptation to the D50 media white
+/// point, with the colorants adapted to D50 as the ICC specification asks
+fn new_rgb_profile(
+    white_point: (f64, f64),
+    [red, green, blue]: [(f64, f64); 3],
+) -> Result<Profile, lcms2::Error> {
+    // the sRGB piecewise curve as an ICC parametric curve of type 4
+    let srgb_trc =
+        ToneCurve::new_parametric(4, &[2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045])
+            .unwrap();
+    let primaries = lcms2::CIExyYTRIPLE {
+        Red: xyy(red),
+        Green: xyy(green),
+        Blue: xyy(blue),
+    };
+    Profile::new_rgb(
+        &xyy(white_point),
+        &primaries,
+        &[&srgb_trc, &srgb_trc, &srgb_trc],
+    )
+}
+
+/// Serializes a profile to the bytes of an ICC file in memory, without saving it to a file
+pub fn profile_to_bytes(profile: &Profile) -> Result<Vec<u8>, lcms2::Error> {
+    profile.icc()
+}
+
+/// Reads the red, green and blue curves back from the VCGT tag of a profile, returning None if
+/// the profile has no VCGT tag
+pub fn read_vcgt_curves(profile: &Profile) -> Option<Vec<Vec<u16>>> {
+    match profile.read_tag(lcms2::TagSignature::VcgtTag) {
+        Tag::VcgtCurves(curves) => Some(
+            curves
+                .iter()
+                .map(|curve| curve.estimated_entries().to_vec())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    /// Tests the entirety of parse_curves() with a known example. The expected curves are kept in
+    /// a JSON fixture, which is regenerated from the current output by running the test with
+    /// the UPDATE_EXPECTED environment variable set
+    fn parsing_example_input_works() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let parsed_result = parse_curves(&input).unwrap();
+
+        let fixture = "test/gimp_test_curve.expected.json";
+        if std::env::var_os("UPDATE_EXPECTED").is_some() {
+            fs::write(fixture, serde_json::to_string(&parsed_result).unwrap()).unwrap();
+        }
+        let expected: Vec<Vec<u16>> =
+            serde_json::from_str(&fs::read_to_string(fixture).unwrap()).unwrap();
+
+        assert_eq!(parsed_result, expected);
+    }
+
+    #[test]
+    fn too_few_curves_is_an_error() {
+        let input = "(samples 2 0 1))\n(samples 2 0 1))\n";
+        assert_eq!(
+            parse_curves(input),
+            Err(CurveParseError::WrongCurveCount(2))
+        );
+    }
+
+    #[test]
+    fn bad_number_is_an_error() {
+        let input = "(samples 2 0 abc))\n".repeat(4);
+        assert_eq!(
+            parse_curves(&input),
+            Err(CurveParseError::NumberParse {
+                token: "abc".to_string(),
+                line: Some(1),
+                channel: Some("value"),
+            })
+        );
+    }
+
+    #[test]
+    fn bad_number_reports_its_line_and_channel() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt")
+            .unwrap()
+            .replacen("0.0039215686274509803", "0.00392x", 1);
+        let err = parse_curves(&input).unwrap_err();
+        assert_eq!(
+            err,
+            CurveParseError::NumberParse {
+                token: "0.00392x".to_string(),
+                line: Some(18),
+                channel: Some("red"),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "could not parse sample value \"0.00392x\" as a number on line 18 in the red channel"
+        );
+    }
+
+    #[test]
+    fn mismatched_channel_lengths_are_resampled() {
+        let input = "(samples 2 0 1))\n(samples 3 0 0.25 1))\n".repeat(2);
+        let channels = parse_channels(&input).unwrap();
+        assert_eq!(channels.gray, vec![0, 32768, 65535]);
+        assert_eq!(channels.red, vec![0, 16384, 65535]);
+    }
+
+    #[test]
+    fn mixed_resolution_fixture_parses() {
+        let input = fs::read_to_string("test/gimp_mixed_resolution_curve.txt").unwrap();
+        let channels = parse_channels(&input).unwrap();
+        for curve in [
+            &channels.gray,
+            &channels.red,
+            &channels.green,
+            &channels.blue,
+        ] {
+            assert_eq!(curve.len(), 512);
+        }
+        assert_eq!(channels.alpha.map(|alpha| alpha.len()), Some(512));
+
+        // the value curve is kept as saved and the colour curves are interpolated
+        let fine = parse_channels(&fs::read_to_string("test/gimp_512_curve.txt").unwrap()).unwrap();
+        assert_eq!(channels.gray, fine.gray);
+        let coarse =
+            parse_channels(&fs::read_to_string("test/gimp_test_curve.txt").unwrap()).unwrap();
+        assert_eq!(channels.red[0], coarse.red[0]);
+        assert_eq!(channels.red[511], coarse.red[255]);
+        assert_eq!(parse_curves(&input).unwrap()[0].len(), 512);
+    }
+
+    #[test]
+    fn parsing_512_samples_works() {
+        let input = fs::read_to_string("test/gimp_512_curve.txt").unwrap();
+        let curves = parse_curves(&input).unwrap();
+
+        assert_eq!(curves.len(), 3);
+        for curve in &curves {
+            assert_eq!(curve.len(), 512);
+            assert_eq!(curve[0], 0);
+            assert_eq!(curve[511], 65535);
+        }
+        // red is brightened over the identity gray curve, green and blue are left as they are
+        assert!(curves[0][256] > curves[1][256] + 5000);
+        assert!(curves[1][256].abs_diff(32832) <= 128);
+    }
+
+    #[test]
+    fn built_profile_has_three_channel_vcgt() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let rgb_curves = parse_curves(&input).unwrap();
+        let icc = build_profile(&rgb_curves, "test profile").unwrap();
+
+        match icc.read_tag(lcms2::TagSignature::VcgtTag) {
+            lcms2::Tag::VcgtCurves(curves) => {
+                assert_eq!(curves.len(), 3);
+                for (curve, expected) in curves.iter().zip(&rgb_curves) {
+                    assert_eq!(curve.estimated_entries(), &expected[..]);
+                }
+            }
+            _ => panic!("profile is missing the VCGT tag"),
+        }
+    }
+
+    #[test]
+    fn parsing_legacy_input_works() {
+        let input = fs::read_to_string("test/gimp_legacy_curve.txt").unwrap();
+        let curves = parse_legacy_curves(&input).unwrap();
+
+        // value, red, green, blue and alpha
+        assert_eq!(curves.len(), 5);
+        assert!(curves.iter().all(|curve| curve.len() == 256));
+        // value curve only has the default end points
+        assert_eq!(curves[0], (0..256).map(|x| x * 257).collect::<Vec<u16>>());
+        // red is lifted to 160 at 128, and held flat before its first point at 16
+        assert_eq!(curves[1][0], 0);
+        assert_eq!(curves[1][16], 0);
+        assert_eq!(curves[1][128], 160 * 257);
+        assert_eq!(curves[1][255], 65535);
+        // blue has its white point pulled down to 230
+        assert_eq!(curves[3][255], 230 * 257);
+
+        let rgb_curves = parse_curves(&input).unwrap();
+        assert_eq!(rgb_curves.len(), 3);
+        assert_eq!(rgb_curves[2][255], 230 * 257);
+    }
+
+    #[test]
+    fn linear_fail_rejects_linear_curves() {
+        let input = fs::read_to_string("test/gimp_linear_curve.txt").unwrap();
+        let options = ParseOptions {
+            linear_fail: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            parse_curves_with_options(&input, &options),
+            Err(CurveParseError::LinearLight)
+        );
+        // without the flag it is only a warning
+        assert!(parse_curves(&input).is_ok());
+    }
+
+    #[test]
+    fn trc_mode_writes_trc_tags_only() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let rgb_curves = parse_curves(&input).unwrap();
+        let options = ProfileOptions {
+            mode: CurveMode::Trc,
+            ..Default::default()
+        };
+        let icc = build_profile_with_options(&rgb_curves, &options).unwrap();
+
+        assert!(!icc.has_tag(lcms2::TagSignature::VcgtTag));
+        match icc.read_tag(lcms2::TagSignature::RedTRCTag) {
+            lcms2::Tag::ToneCurve(curve) => {
+                assert_eq!(curve.estimated_entries(), &rgb_curves[0][..])
+            }
+            _ => panic!("profile is missing the red TRC tag"),
+        }
+    }
+
+    #[test]
+    fn alpha_curve_is_included_and_stored() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        assert_eq!(parse_curves(&input).unwrap().len(), 3);
+
+        let options = ParseOptions {
+            include_alpha: true,
+            ..Default::default()
+        };
+        let curves = parse_curves_with_options(&input, &options).unwrap();
+        assert_eq!(curves.len(), 4);
+        // alpha is not composed with the value curve
+        assert_eq!(curves[3][128], 32896);
+
+        let icc = build_profile(&curves, "alpha").unwrap();
+        let data = private_tag::read_private_tag(&icc, private_tag::ALPHA_CURVE_TAG).unwrap();
+        let alpha: Vec<u16> = data
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(alpha, curves[3]);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        assert_eq!(
+            parse_u16_curve_vec("-0.01 0.5 1.0001").unwrap(),
+            vec![0, 32768, 65535]
+        );
+    }
+
+    #[test]
+    fn non_finite_values_are_rejected() {
+        for token in ["NaN", "inf", "-infinity"] {
+            assert_eq!(
+                parse_u16_curve_vec(&format!("0 {token} 1")),
+                Err(CurveParseError::number(token))
+            );
+        }
+    }
+
+    #[test]
+    fn single_sample_curves_are_rejected() {
+        let input: String = ["value", "red", "green", "blue"]
+            .iter()
+            .map(|channel| {
+                format!("(channel {channel})\n(curve\n    (n-samples 1)\n    (samples 1 0.5))\n")
+            })
+            .collect();
+        assert_eq!(
+            parse_curves(&input),
+            Err(CurveParseError::BadSampleLength {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn mixed_whitespace_and_exponents_are_parsed() {
+        let input = fs::read_to_string("test/gimp_whitespace_curve.txt").unwrap();
+        let expected = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+
+        assert_eq!(
+            parse_curves(&input).unwrap(),
+            parse_curves(&expected).unwrap()
+        );
+    }
+
+    #[test]
+    fn gamma_profile_has_power_curves() {
+        let icc = build_gamma_profile(2.2, &ProfileOptions::default()).unwrap();
+
+        match icc.read_tag(lcms2::TagSignature::VcgtTag) {
+            lcms2::Tag::VcgtCurves(curves) => {
+                for curve in curves {
+                    assert!((curve.eval(0.5f32) - 0.5f32.powf(2.2)).abs() < 0.001);
+                }
+            }
+            _ => panic!("profile is missing the VCGT tag"),
+        }
+    }
+
+    #[test]
+    fn text_tags_are_written_when_given() {
+        let options = ProfileOptions {
+            manufacturer: Some("Tarky".to_string()),
+            model: Some("Monitor".to_string()),
+            ..Default::default()
+        };
+        let icc = build_gamma_profile(1.0, &options).unwrap();
+
+        let text = |signature| match icc.read_tag(signature) {
+            lcms2::Tag::MLU(mlu) => mlu.text(Locale::none()).unwrap(),
+            _ => panic!("profile is missing text tag {signature:?}"),
+        };
+        assert_eq!(text(lcms2::TagSignature::DeviceMfgDescTag), "Tarky");
+        assert_eq!(text(lcms2::TagSignature::DeviceModelDescTag), "Monitor");
+        // lcms2's copyright is kept
+        assert!(icc.has_tag(lcms2::TagSignature::CopyrightTag));
+    }
+
+    #[test]
+    fn fixed_creation_date_gives_identical_bytes() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let rgb_curves = parse_curves(&input).unwrap();
+        let options = ProfileOptions {
+            creation_date: Some("2024-01-31T12:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let other_date = ProfileOptions {
+            creation_date: Some("2025-06-15T08:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let first =
+            profile_to_bytes(&build_profile_with_options(&rgb_curves, &options).unwrap()).unwrap();
+        let second =
+            profile_to_bytes(&build_profile_with_options(&rgb_curves, &options).unwrap()).unwrap();
+        let other =
+            profile_to_bytes(&build_profile_with_options(&rgb_curves, &other_date).unwrap())
+                .unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(&first[24..30], &[0x07, 0xe8, 0, 1, 0, 31]);
+        // only the date differs, so it's the date that decides the bytes
+        assert_ne!(first, other);
+        assert_eq!(first[..24], other[..24]);
+        assert_eq!(first[36..], other[36..]);
+    }
+
+    #[test]
+    fn version_2_uses_v2_tag_types() {
+        let options = ProfileOptions {
+            version: Some(IccVersion::V2),
+            ..Default::default()
+        };
+        let bytes = profile_to_bytes(&build_gamma_profile(2.2, &options).unwrap()).unwrap();
+
+        assert_eq!(&bytes[8..10], &[0x02, 0x10]);
+        // description is saved as a textDescriptionType
+        assert!(bytes.windows(4).any(|it| it == b"desc"));
+        assert!(!bytes.windows(4).any(|it| it == b"mluc"));
+        assert!(Profile::new_icc(&bytes).is_ok());
+    }
+
+    #[test]
+    fn input_format_is_detected() {
+        let acv = fs::read("test/photoshop_curve.acv").unwrap();
+        let gimp = fs::read("test/gimp_test_curve.txt").unwrap();
+
+        assert_eq!(
+            InputFormat::detect(Path::new("a.acv"), &gimp),
+            InputFormat::Acv
+        );
+        assert_eq!(InputFormat::detect(Path::new("-"), &acv), InputFormat::Acv);
+        assert_eq!(
+            InputFormat::detect(Path::new("-"), &gimp),
+            InputFormat::Gimp
+        );
+
+        let options = ParseOptions::default();
+        assert_eq!(
+            detect_and_parse(Path::new("-"), &acv, None, &options).unwrap(),
+            acv::parse_acv(&acv).unwrap()
+        );
+        assert!(detect_and_parse(Path::new("-"), &acv, Some(InputFormat::Gimp), &options).is_err());
+    }
+
+    #[test]
+    fn vcgt_curves_are_read_back() {
+        let icc = build_gamma_profile(1.0, &ProfileOptions::default()).unwrap();
+        let curves = read_vcgt_curves(&icc).unwrap();
+
+        assert_eq!(curves.len(), 3);
+        assert_eq!(curves[0][0], 0);
+        assert_eq!(*curves[0].last().unwrap(), 65535);
+        assert_eq!(read_vcgt_curves(&Profile::new_srgb()), None);
+    }
+
+    #[test]
+    fn vcgt_round_trips_through_serialization() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let rgb_curves = parse_curves(&input).unwrap();
+        let icc = build_profile(&rgb_curves, "round trip").unwrap();
+
+        let bytes = profile_to_bytes(&icc).unwrap();
+        let reloaded = Profile::new_icc(&bytes).unwrap();
+        let curves = read_vcgt_curves(&reloaded).unwrap();
+
+        assert_eq!(curves.len(), 3);
+        for (curve, expected) in curves.iter().zip(&rgb_curves) {
+            assert_eq!(curve.len(), expected.len());
+            for (&a, &b) in curve.iter().zip(expected) {
+                // lcms2 tabulates the curves again when saving
+                assert!(a.abs_diff(b) <= 1, "{a} != {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn short_gray_curve_stays_in_bounds() {
+        assert_eq!(scale_index(0, 4), 0);
+        assert_eq!(scale_index(u16::MAX, 4), 3);
+        assert_eq!(scale_index(u16::MAX, 200), 199);
+        assert_eq!(scale_index(u16::MAX, 1), 0);
+
+        let gray = vec![0, 1000, 2000, 3000];
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        let result = compose_gray_over_channel(&gray, &ramp);
+        assert_eq!(result[0], 0);
+        assert_eq!(result[255], 3000);
+    }
+
+    #[test]
+    fn too_few_curves_are_an_error() {
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        assert_eq!(
+            build_profile(&vec![ramp; 2], "x").err(),
+            Some(ProfileError::Curves(CurveParseError::WrongCurveCount(2)))
+        );
+        assert_eq!(
+            build_grayscale_profile(&[0], &ProfileOptions::default()).err(),
+            Some(ProfileError::Curves(CurveParseError::BadSampleLength {
+                expected: 2,
+                found: 1
+            }))
+        );
+    }
+
+    #[test]
+    fn gray_is_applied_after_channel() {
+        let gray = vec![0, 10000, 20000, 65535];
+        // a channel that swaps the two middle values
+        let channel = vec![0, 43690, 21845, 65535];
+
+        assert_eq!(
+            compose_gray_over_channel(&gray, &channel),
+            vec![0, 20000, 10000, 65535]
+        );
+        // channel values between samples of the gray curve are interpolated
+        assert_eq!(compose_gray_over_channel(&gray, &[32768]), vec![15000]);
+    }
+
+    #[test]
+    fn skip_gray_returns_raw_channels() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let curves = parse_sample_curves(&input, 0, false, false).unwrap();
+        let options = ParseOptions {
+            skip_gray: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            parse_curves_with_options(&input, &options).unwrap(),
+            curves[1..4].to_vec()
+        );
+        assert_ne!(parse_curves(&input).unwrap(), curves[1..4].to_vec());
+    }
+
+    #[test]
+    fn channels_are_picked_by_label() {
+        let input = "(channel value)\n(samples 2 0 1))\n\
+                     (channel alpha)\n(samples 2 0 0))\n\
+                     (channel blue)\n(samples 2 0 0.25))\n\
+                     (channel red)\n(samples 2 0 0.75))\n\
+                     (channel green)\n(samples 2 0 0.5))\n";
+        // with only 2 samples the value curve would flatten the colour curves
+        let options = ParseOptions {
+            include_alpha: true,
+            skip_gray: true,
+            ..Default::default()
+        };
+        let curves = parse_curves_with_options(input, &options).unwrap();
+
+        assert_eq!(
+            curves,
+            vec![vec![0, 49151], vec![0, 32768], vec![0, 16384], vec![0, 0]]
+        );
+    }
+
+    #[test]
+    fn missing_labelled_channel_is_an_error() {
+        let input = "(channel value)\n(samples 2 0 1))\n\
+                     (channel red)\n(samples 2 0 1))\n\
+                     (channel green)\n(samples 2 0 1))\n\
+                     (channel alpha)\n(samples 2 0 1))\n";
+        assert_eq!(
+            parse_curves(input),
+            Err(CurveParseError::MissingChannel("blue"))
+        );
+    }
+
+    #[test]
+    fn grayscale_profile_has_gray_trc() {
+        let gray: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        let icc = build_grayscale_profile(&gray, &ProfileOptions::default()).unwrap();
+
+        assert_eq!(icc.color_space(), lcms2::ColorSpaceSignature::GrayData);
+        assert!(icc.has_tag(lcms2::TagSignature::GrayTRCTag));
+        assert!(!icc.has_tag(lcms2::TagSignature::VcgtTag));
+        assert_eq!(
+            icc.info(lcms2::InfoType::Description, lcms2::Locale::none()),
+            Some(ProfileOptions::default().description)
+        );
+    }
+
+    #[test]
+    fn base_profile_keeps_primaries() {
+        let base = Profile::new_rgb(
+            &lcms2::CIExyY {
+                x: 0.3127,
+                y: 0.329,
+                Y: 1.0,
+            },
+            &lcms2::CIExyYTRIPLE {
+                Red: lcms2::CIExyY {
+                    x: 0.68,
+                    y: 0.32,
+                    Y: 1.0,
+                },
+                Green: lcms2::CIExyY {
+                    x: 0.265,
+                    y: 0.69,
+                    Y: 1.0,
+                },
+                Blue: lcms2::CIExyY {
+                    x: 0.15,
+                    y: 0.06,
+                    Y: 1.0,
+                },
+            },
+            &[
+                &ToneCurve::new(2.2),
+                &ToneCurve::new(2.2),
+                &ToneCurve::new(2.2),
+            ],
+        )
+        .unwrap();
+        let options = ProfileOptions {
+            base_profile: Some(base.icc().unwrap()),
+            ..Default::default()
+        };
+        let icc = build_gamma_profile(1.5, &options).unwrap();
+
+        assert!(read_vcgt_curves(&icc).is_some());
+        let red = |profile: &Profile| match profile.read_tag(lcms2::TagSignature::RedColorantTag) {
+            Tag::CIEXYZ(xyz) => (xyz.X, xyz.Y, xyz.Z),
+            _ => panic!("red colorant missing"),
+        };
+        // the base profile was not serialized yet, so its colorants have not been rounded
+        let base = Profile::new_icc(&options.base_profile.unwrap()).unwrap();
+        assert_eq!(red(&icc), red(&base));
+        assert_ne!(red(&icc), red(&Profile::new_srgb()));
+    }
+
+    #[test]
+    fn invalid_base_profile_and_primaries_are_errors() {
+        let invalid_base = ProfileOptions {
+            base_profile: Some(b"not a profile".to_vec()),
+            ..Default::default()
+        };
+        assert!(build_gamma_profile(2.2, &invalid_base).is_err());
+
+        let collinear = ProfileOptions {
+            primaries: Some([(0.2, 0.2), (0.4, 0.4), (0.6, 0.6)]),
+            ..Default::default()
+        };
+        assert!(build_gamma_profile(2.2, &collinear).is_err());
+    }
+
+    #[test]
+    fn wrong_declared_sample_count_is_an_error() {
+        let input = fs::read_to_string("test/gimp_bad_count_curve.txt").unwrap();
+        assert_eq!(
+            parse_curves(&input),
+            Err(CurveParseError::SampleCountMismatch {
+                declared: 256,
+                found: 255
+            })
+        );
+    }
+
+    #[test]
+    fn empty_samples_list_is_an_error() {
+        let input = fs::read_to_string("test/gimp_empty_samples_curve.txt").unwrap();
+        assert_eq!(
+            parse_curves(&input),
+            Err(CurveParseError::EmptyCurve("green"))
+        );
+    }
+
+    #[test]
+    fn bom_and_crlf_are_handled() {
+        let input = fs::read_to_string("test/gimp_bom_crlf_curve.txt").unwrap();
+        let expected = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+
+        assert!(input.starts_with('\u{feff}') && input.contains("\r\n"));
+        assert_eq!(parse_curves(&input), parse_curves(&expected));
+        // value, red, green and blue are all found, and alpha after them
+        assert!(parse_channels(&input).unwrap().alpha.is_some());
+    }
+
+    #[test]
+    fn text_tags_use_the_locale() {
+        let options = ProfileOptions {
+            description: "Profiili".to_string(),
+            locale: Locale::new("fi_FI"),
+            ..Default::default()
+        };
+        let icc = build_gamma_profile(1.0, &options).unwrap();
+
+        match icc.read_tag(lcms2::TagSignature::ProfileDescriptionTag) {
+            lcms2::Tag::MLU(mlu) => {
+                let translations = mlu.tanslations();
+                assert_eq!(translations.len(), 1);
+                assert_eq!(translations[0].to_string(), "fi_FI");
+            }
+            _ => panic!("description is not an MLU"),
+        }
+    }
+
+    #[test]
+    fn description_has_all_translations() {
+        let options = ProfileOptions {
+            description: "Profile".to_string(),
+            localized_descriptions: vec![
+                (Locale::new("de_DE"), "Profil".to_string()),
+                (Locale::new("fi_FI"), "Profiili".to_string()),
+            ],
+            ..Default::default()
+        };
+        let icc = build_gamma_profile(1.0, &options).unwrap();
+
+        match icc.read_tag(lcms2::TagSignature::ProfileDescriptionTag) {
+            lcms2::Tag::MLU(mlu) => {
+                assert_eq!(mlu.tanslations().len(), 3);
+                assert_eq!(mlu.text(Locale::new("de_DE")).unwrap(), "Profil");
+                assert_eq!(mlu.text(Locale::new("fi_FI")).unwrap(), "Profiili");
+                assert_eq!(mlu.text(Locale::none()).unwrap(), "Profile");
+            }
+            _ => panic!("description is not an MLU"),
+        }
+    }
+
+    #[test]
+    fn raw_samples_are_not_clamped() {
+        let input = "(samples 2 -0.5 1))\n".repeat(3) + "(samples 2 0 1.5))\n";
+        let raw = parse_raw_samples(&input, 0).unwrap();
+
+        assert_eq!(raw.len(), 4);
+        assert_eq!(raw[0], vec![-0.5, 1.0]);
+        assert_eq!(raw[3], vec![0.0, 1.5]);
+    }
+
+    #[test]
+    fn both_mode_writes_vcgt_and_trc_tags() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let rgb_curves = parse_curves(&input).unwrap();
+        let options = ProfileOptions {
+            mode: CurveMode::Both,
+            ..Default::default()
+        };
+        let icc = build_profile_with_options(&rgb_curves, &options).unwrap();
+
+        assert!(icc.has_tag(lcms2::TagSignature::VcgtTag));
+        match icc.read_tag(lcms2::TagSignature::RedTRCTag) {
+            lcms2::Tag::ToneCurve(curve) => {
+                assert_eq!(curve.estimated_entries(), &rgb_curves[0][..])
+            }
+            _ => panic!("profile is missing the red TRC tag"),
+        }
+    }
+
+    #[test]
+    fn u8_scaling_rounds() {
+        assert_eq!(scale_u16_to_u8_range(0), 0);
+        assert_eq!(scale_u16_to_u8_range(32768), 128);
+        assert_eq!(scale_u16_to_u8_range(u16::MAX), 255);
+    }
+
+    #[test]
+    fn lerp_lookup_interpolates_between_entries() {
+        let table = [0, 1000, 3000];
+        assert_eq!(lerp_lookup(&table, 0.0), 0);
+        assert_eq!(lerp_lookup(&table, 0.25), 500);
+        assert_eq!(lerp_lookup(&table, 0.75), 2000);
+        assert_eq!(lerp_lookup(&table, 1.0), 3000);
+        assert_eq!(lerp_lookup(&[42], 0.5), 42);
+    }
+
+    #[test]
+    fn gray_composes_over_a_longer_channel() {
+        // identity gray curve, which should leave the channel as it is apart from rounding
+        let gray: Vec<u16> = (0..256).map(|i| i * 257).collect();
+        let channel: Vec<u16> = (0..512u32)
+            .map(|i| (i * u16::MAX as u32 / 511) as u16)
+            .collect();
+        let composed = compose_gray_over_channel(&gray, &channel);
+
+        assert_eq!(composed.len(), 512);
+        for (composed, original) in composed.iter().zip(&channel) {
+            assert!(composed.abs_diff(*original) <= 1);
+        }
+    }
+
+    #[test]
+    fn source_is_stored_in_private_tag() {
+        let options = ProfileOptions {
+            source: Some("curve.txt".to_string()),
+            creation_date: Some("2024-01-31T12:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+        let icc = build_gamma_profile(2.2, &options).unwrap();
+
+        assert_eq!(
+            private_tag::read_private_tag(&icc, private_tag::SOURCE_TAG),
+            Some(b"curve.txt".to_vec())
+        );
+        // the date is still written after the tag is added
+        assert_eq!(&profile_to_bytes(&icc).unwrap()[24..26], &[0x07, 0xe8]);
+
+        let icc = build_gamma_profile(2.2, &ProfileOptions::default()).unwrap();
+        assert_eq!(
+            private_tag::read_private_tag(&icc, private_tag::SOURCE_TAG),
+            None
+        );
+    }
+
+    #[test]
+    fn target_bits_are_stored_in_private_tag() {
+        let options = ProfileOptions {
+            target_bits: Some(10),
+            ..Default::default()
+        };
+        let icc = build_gamma_profile(2.2, &options).unwrap();
+
+        assert_eq!(
+            private_tag::read_private_tag(&icc, private_tag::TARGET_BITS_TAG),
+            Some(vec![10])
+        );
+    }
+
+    #[test]
+    fn eight_bit_curves_keep_endpoints() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let rgb_curves = parse_curves(&input).unwrap();
+        let options = ProfileOptions {
+            bit_depth: BitDepth::Eight,
+            ..Default::default()
+        };
+        let icc = build_profile_with_options(&rgb_curves, &options).unwrap();
+        let curves = read_vcgt_curves(&icc).unwrap();
+
+        for (curve, expected) in curves.iter().zip(&rgb_curves) {
+            assert_eq!(curve[0], 0);
+            assert_eq!(curve[255], 65535);
+            for (&a, &b) in curve.iter().zip(expected) {
+                assert_eq!(a % 257, 0, "{a} is not an 8-bit level");
+                assert!(a.abs_diff(b) <= 129, "{a} != {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn rendering_intent_is_set_in_header() {
+        let options = ProfileOptions {
+            intent: Some(RenderingIntent::Saturation),
+            ..Default::default()
+        };
+        let bytes = profile_to_bytes(&build_gamma_profile(2.2, &options).unwrap()).unwrap();
+        let reloaded = Profile::new_icc(&bytes).unwrap();
+
+        assert_eq!(
+            reloaded.header_rendering_intent(),
+            lcms2::Intent::Saturation
+        );
+    }
+
+    #[test]
+    fn curve_block_is_selected_by_index() {
+        let input = fs::read_to_string("test/gimp_two_curves.txt").unwrap();
+        let options = ParseOptions {
+            skip_gray: true,
+            ..Default::default()
+        };
+
+        assert_eq!(parse_channels(&input).unwrap().gray[1], 13107);
+        assert_eq!(parse_channels_at(&input, 1).unwrap().gray[1], 32768);
+        // the colour curves of each block are the same
+        assert_eq!(
+            parse_curves_with_options(&input, &options).unwrap(),
+            parse_curves_with_options(
+                &input,
+                &ParseOptions {
+                    curve_index: 1,
+                    ..options.clone()
+                }
+            )
+            .unwrap()
+        );
+        assert!(matches!(
+            parse_channels_at(&input, 2),
+            Err(CurveParseError::CurveIndex {
+                index: 2,
+                blocks: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn unlabelled_blocks_must_be_complete() {
+        let input = "(samples 2 0 1))\n".repeat(8);
+        assert_eq!(parse_raw_samples(&input, 1).unwrap().len(), 4);
+
+        let input = "(samples 2 0 1))\n".repeat(7);
+        assert!(matches!(
+            parse_curves(&input),
+            Err(CurveParseError::IncompleteBlocks(7))
+        ));
+    }
+
+    #[test]
+    fn huge_sample_count_is_an_error() {
+        let input = "(samples 99999999999999999999999 0 1))\n".repeat(4);
+        assert!(matches!(
+            parse_curves(&input),
+            Err(CurveParseError::NumberParse { .. })
+        ));
+    }
+
+    #[test]
+    fn trailing_attributes_are_ignored() {
+        let input = fs::read_to_string("test/gimp_trailing_attribute_curve.txt").unwrap();
+        let channels = parse_channels(&input).unwrap();
+
+        assert_eq!(channels.gray, vec![0, 16384, 32768, 65535]);
+        assert_eq!(channels.red, vec![0, 21845, 43690, 65535]);
+        assert_eq!(channels.blue, channels.red);
+    }
+
+    #[test]
+    fn skip_gray_channels_leaves_only_those_raw() {
+        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        let raw = parse_sample_curves(&input, 0, false, false).unwrap();
+        let composed = parse_curves(&input).unwrap();
+        let options = ParseOptions {
+            skip_gray_channels: [false, false, true],
+            ..Default::default()
+        };
+        let curves = parse_curves_with_options(&input, &options).unwrap();
+
+        assert_eq!(curves[0], composed[0]);
+        assert_eq!(curves[1], composed[1]);
+        assert_eq!(curves[2], raw[3]);
+    }
+
+    #[test]
+    fn generated_profile_can_be_the_base_profile() {
+        let curves = vec![(0..256).map(|i| i * 257).collect::<Vec<u16>>(); 4];
+        let options = ProfileOptions {
+            source: Some("first.txt".to_string()),
+            ..Default::default()
+        };
+        let first = build_profile_with_options(&curves, &options).unwrap();
+
+        // the private tags of the base profile are replaced instead of added a second time
+        let options = ProfileOptions {
+            source: Some("second.txt".to_string()),
+            base_profile: Some(first.icc().unwrap()),
+            merge_vcgt: true,
+            ..Default::default()
+        };
+        let second = build_profile_with_options(&curves, &options).unwrap();
+        assert_eq!(
+            private_tag::read_private_tag(&second, private_tag::SOURCE_TAG),
+            Some(b"second.txt".to_vec())
+        );
+        assert!(private_tag::read_private_tag(&second, private_tag::ALPHA_CURVE_TAG).is_some());
+    }
+
+    #[test]
+    fn merge_vcgt_applies_curves_after_base() {
+        let halved: Vec<u16> = (0..256).map(|i| i * 128).collect();
+        let base = build_profile(&vec![halved.clone(); 3], "base").unwrap();
+        let inverted: Vec<u16> = (0..256).rev().map(|i| i * 257).collect();
+        let mut options = ProfileOptions {
+            base_profile: Some(base.icc().unwrap()),
+            merge_vcgt: true,
+            ..Default::default()
+        };
+
+        let merged = read_vcgt_curves(
+            &build_profile_with_options(&vec![inverted.clone(); 3], &options).unwrap(),
+        )
+        .unwrap();
+        for (i, &base) in halved.iter().enumerate() {
+            assert!(merged[0][i].abs_diff(65535 - base) <= 2, "entry {i}");
+        }
+
+        options.merge_vcgt = false;
+        let replaced = read_vcgt_curves(
+            &build_profile_with_options(&vec![inverted.clone(); 3], &options).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(replaced[0], inverted);
+    }
+
+    #[test]
+    fn trailing_padding_is_truncated() {
+        let input = fs::read_to_string("test/gimp_padded_curve.txt").unwrap();
+        assert_eq!(
+            parse_curves(&input),
+            Err(CurveParseError::SampleCountMismatch {
+                declared: 256,
+                found: 259
+            })
+        );
+
+        let options = ParseOptions {
+            truncate_trailing_padding: true,
+            ..Default::default()
+        };
+        let expected = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
+        assert_eq!(
+            parse_curves_with_options(&input, &options).unwrap(),
+            parse_curves(&expected).unwrap()
+        );
+
+        // padding that isn't all 1 is still an error
+        let uneven = input.replacen(" 1 1 1))", " 1 0.5 1))", 1);
+        assert!(parse_curves_with_options(&uneven, &options).is_err());
+    }
+
+    #[test]
+    fn primaries_set_colorants() {
+        let red = |profile: &Profile| match profile.read_tag(lcms2::TagSignature::RedColorantTag) {
+            Tag::CIEXYZ(xyz) => (xyz.X, xyz.Y, xyz.Z),
+            _ => panic!("red colorant missing"),
+        };
+        let curve = transform::identity_curve(256);
+        let build = |white_point, primaries| {
+            let options = ProfileOptions {
+                mode: CurveMode::Trc,
+                white_point,
+                primaries,
+                ..Default::default()
+            };
+            build_profile_with_options(&vec![curve.clone(); 3], &options).unwrap()
+        };
+
+        let srgb = build(None, None);
+        let explicit = build(Some(D65), Some(SRGB_PRIMARIES));
+        let (x, y, z) = red(&srgb);
+        let (ex, ey, ez) = red(&explicit);
+        assert!((x - ex).abs() < 1e-3 && (y - ey).abs() < 1e-3 && (z - ez).abs() < 1e-3);
+
+        let wide = build(None, Some([(0.708, 0.292), (0.17, 0.797), (0.131, 0.046)]));
+        assert!(red(&wide).0 > x + 0.05);
+        // the TRC curves from the file still replace the sRGB transfer function
+        assert!(matches!(
+            wide.read_tag(lcms2::TagSignature::RedTRCTag),
+            Tag::ToneCurve(tc) if tc.estimated_entries().len() == 256
+        ));
+
+        let d50 = build(Some(D50), None);
+        assert_ne!(red(&d50), red(&srgb));
+    }
+
+    #[test]
+    fn icm_and_icc_files_are_identical() {
+        let mut icc = build_gamma_profile(1.2, &ProfileOptions::default()).unwrap();
+        let dir = std::env::temp_dir();
+        let paths = ["icc", "icm"]
+            .map(|ext| dir.join(format!("extension_test_{}.{ext}", std::process::id())));
+        for path in &paths {
+            icc.save_profile_to_file(path).unwrap();
+        }
+        let [icc_bytes, icm_bytes] = paths.clone().map(|path| fs::read(path).unwrap());
+        for path in &paths {
+            fs::remove_file(path).unwrap();
+        }
+        assert_eq!(icc_bytes, icm_bytes);
+        assert_eq!(icc_bytes, profile_to_bytes(&icc).unwrap());
+    }
+
+    #[test]
+    fn gzip_input_is_decompressed() {
+        let compressed = fs::read("test/gimp_test_curve.txt.gz").unwrap();
+        let plain = fs::read("test/gimp_test_curve.txt").unwrap();
+        assert_eq!(decompress_gzip(compressed).unwrap(), plain);
+        assert_eq!(decompress_gzip(plain.clone()).unwrap(), plain);
+        assert!(decompress_gzip(vec![0x1f, 0x8b, 0]).is_err());
+
+        assert_eq!(
+            InputFormat::from_path(Path::new("curve.txt.gz")),
+            Some(InputFormat::Gimp)
+        );
+        assert_eq!(InputFormat::from_path(Path::new("curve.gz")), None);
+    }
+
+    #[test]
+    fn gray_only_file_gives_grayscale_curve() {
+        let path = Path::new("test/gimp_gray_only_curve.txt");
+        let input = fs::read(path).unwrap();
+        let full = fs::read("test/gimp_test_curve.txt").unwrap();
+        assert!(is_gray_only(path, &input, None));
+        assert!(!is_gray_only(Path::new("curve.txt"), &full, None));
+
+        let gray = parse_gray_curve(path, &input, None, &ParseOptions::default()).unwrap();
+        let expected = parse_channels(&String::from_utf8_lossy(&full))
+            .unwrap()
+            .gray;
+        assert_eq!(gray, expected);
+        // an RGB profile still needs the colour curves
+        assert_eq!(
+            detect_and_parse(path, &input, None, &ParseOptions::default()),
+            Err(CurveParseError::MissingChannel("red"))
+        );
+    }
+}
@@ -1,110 +1,1936 @@
-use clap::Parser;
-use lcms2::{Locale, Profile, Tag, ToneCurve, MLU};
-use regex::Regex;
-use std::{fs, path::PathBuf};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::{
+    error::ErrorKind, parser::ValueSource, Arg, CommandFactory, FromArgMatches, Parser, Subcommand,
+    ValueEnum,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use lcms2::{Locale, Profile};
+use log::{debug, info, warn, Level};
+use rs_gimp_to_icc::{
+    analysis, build_gamma_profile, build_grayscale_profile, build_profile_with_options,
+    date::CreationDate, decompress_gzip, detect_and_parse, detect_and_parse_channels, export,
+    is_gray_only, parse_gray_curve, parse_raw_samples, private_tag, profile_to_bytes,
+    read_vcgt_curves, transform, validate, BitDepth, CurveMode, CurveParseError, IccVersion,
+    InputFormat, ParseOptions, ProfileError, ProfileOptions, RenderingIntent, CHANNEL_NAMES, D50,
+    D65, SAMPLE_CHANNELS, SRGB_PRIMARIES,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    env,
+    ffi::OsString,
+    fmt, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Description used when none is given on the command line
+const DEFAULT_DESCRIPTION: &str = "Custom gamma ICC profile";
+/// Description used for --identity when none is given on the command line
+const IDENTITY_DESCRIPTION: &str = "Identity VCGT reset";
+/// Description used for --reset-srgb when none is given on the command line
+const SRGB_RESET_DESCRIPTION: &str = "sRGB VCGT reset";
+/// Output file name used when none is given on the command line
+const DEFAULT_OUTPUT: &str = "out.icc";
+/// Amount of entries in generated curves when --samples isn't given, as many as GIMP saves
+const DEFAULT_SAMPLES: u32 = 256;
+/// Steps printed for --print-loader-hint
+const LOADER_HINT: &str = r#"Windows only applies the gamma table (VCGT) of the default profile of a display when
+something loads it onto the graphics card. To have Windows load it at every login:
+
+1. Install the profile by right clicking it and choosing "Install Profile", or by copying it to
+   C:\Windows\System32\spool\drivers\color
+2. Open Colour Management (colorcpl.exe), pick the display on the Devices tab, tick "Use my
+   settings for this device", add the profile and choose "Set as Default Profile"
+3. On the Advanced tab, click "Change system defaults...", and on its Advanced tab tick
+   "Use Windows display calibration". This is the DWORD CalibrationManagementEnabled = 1 under
+   HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\Windows NT\CurrentVersion\ICM\Calibration
+4. The scheduled task \Microsoft\Windows\WindowsColorSystem\Calibration Loader then loads the
+   table at login. To load it right away without logging out, run:
+   schtasks /Run /TN "\Microsoft\Windows\WindowsColorSystem\Calibration Loader"
+
+Some programs, such as full screen games, reset the gamma table. A third party loader that
+reapplies it, like the DisplayCAL profile loader, helps with that.
+"#;
+/// Output file name used with --icm when none is given on the command line
+const DEFAULT_ICM_OUTPUT: &str = "out.icm";
 
 #[derive(Parser, Debug)]
-#[command(name = "GIMP Curve to ICC")]
+#[command(
+    name = "GIMP Curve to ICC",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// Input file name
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input file name, or "-" to read from stdin. Curves generated with e.g. --gamma have no
+    /// input file, and are written to --output
+    #[arg(
+        required_unless_present_any = ["batch", "gamma", "identity", "reset_srgb", "merge_files", "curve_base64", "print_loader_hint"],
+        conflicts_with_all = ["gamma", "identity", "reset_srgb", "merge_files", "curve_base64"]
+    )]
+    curves_input: Option<PathBuf>,
+
+    /// Output file name, or "-" to write the profile to stdout [default: out.icc, or out.icm
+    /// with --icm]
     #[arg()]
-    curves_input: PathBuf,
+    icc_output: Option<PathBuf>,
+
+    /// Output file name, the same as the output file argument, for the profiles of curves
+    /// generated with e.g. --gamma, which have no input file argument
+    #[arg(short, long, value_name = "FILE", conflicts_with_all = ["icc_output", "batch"])]
+    output: Option<PathBuf>,
+
+    /// Description or name that will appear in Windows' colour management menu. Defaults to the
+    /// name of the input file without its extension, or to "Custom gamma ICC profile" for stdin
+    /// and generated curves
+    #[arg(short, long = "description")]
+    description: Option<String>,
+
+    /// Copyright notice written to the profile
+    #[arg(long)]
+    copyright: Option<String>,
+
+    /// Device manufacturer written to the profile
+    #[arg(long)]
+    manufacturer: Option<String>,
+
+    /// Device model written to the profile
+    #[arg(long)]
+    model: Option<String>,
+
+    /// Creation date written to the profile header as an RFC 3339 timestamp, e.g.
+    /// 2024-01-31T12:00:00Z. Falls back to the SOURCE_DATE_EPOCH environment variable, and then
+    /// to the current time
+    #[arg(long)]
+    date: Option<CreationDate>,
+
+    /// Existing profile, e.g. a calibration profile, to write the curves and descriptions onto
+    /// instead of a new sRGB profile. Its primaries and white point are kept
+    #[arg(long, value_name = "FILE.icc")]
+    base_profile: Option<PathBuf>,
+
+    /// White point of the profile: d65, d50 or a custom CIE xy chromaticity like 0.3127,0.329.
+    /// Defaults to D65 like sRGB
+    #[arg(long, value_name = "WHITE", value_parser = parse_white_point, conflicts_with = "base_profile")]
+    white_point: Option<(f64, f64)>,
+
+    /// Red, green and blue primaries of the profile, which decide how colour managed
+    /// applications interpret it in TRC mode: srgb, p3, adobe, rec2020 or custom CIE xy
+    /// chromaticities like 0.68,0.32,0.265,0.69,0.15,0.06. Defaults to the sRGB primaries
+    #[arg(long, value_name = "PRIMARIES", value_parser = parse_primaries, conflicts_with_all = ["base_profile", "grayscale"])]
+    primaries: Option<[(f64, f64); 3]>,
+
+    /// Apply the curves on top of the VCGT tag of the base profile, e.g. to adjust a calibrated
+    /// display, instead of replacing it
+    #[arg(long, requires = "base_profile")]
+    merge_vcgt: bool,
+
+    /// Translation of the description for a language and country, e.g. de-DE=MeinProfil. Can be
+    /// given several times, and the --description text is kept for other languages
+    #[arg(long, value_name = "LANG-COUNTRY=TEXT", value_parser = parse_localized_text)]
+    description_locale: Vec<(Locale, String)>,
+
+    /// Language and country of the description and other text tags, e.g. en-US. The text is
+    /// written without a locale by default
+    #[arg(long, value_name = "LANG-COUNTRY", value_parser = parse_locale)]
+    locale: Option<Locale>,
+
+    /// ICC version of the profile. Defaults to 4, with which lcms2 creates profiles
+    #[arg(long, value_enum)]
+    icc_version: Option<IccVersion>,
+
+    /// Precision of the curves in the profile. With 8, each value is rounded to one of 256
+    /// levels for older display utilities that read the gamma table as 8-bit entries
+    #[arg(long, value_enum, default_value_t = BitDepth::Sixteen, conflicts_with = "gamma")]
+    bit_depth: BitDepth,
+
+    /// Rendering intent in the profile header. The one lcms2 gives sRGB profiles, or the one of
+    /// the --base-profile, is kept by default
+    #[arg(long, value_enum)]
+    intent: Option<RenderingIntent>,
+
+    /// Bit depth of the panel the curves are meant for: 8, 10, 12 or 16. Recorded in the profile
+    /// as a hint, and only changes the curves with --quantize. Defaults to full 16-bit curves
+    #[arg(long, value_name = "BITS", value_parser = parse_target_bits)]
+    target_bits: Option<u8>,
+
+    /// Round the red, green and blue curves to the levels of the --target-bits panel, so that
+    /// the profile doesn't imply precision the panel doesn't have
+    #[arg(long, requires = "target_bits", conflicts_with = "bit_depth")]
+    quantize: bool,
+
+    /// Print the parsed curves to stdout instead of building a profile, one channel per line as
+    /// space separated 16-bit values
+    #[arg(long, conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output"])]
+    dump: bool,
+
+    /// Print the output of each curve at 0%, 25%, 50%, 75% and 100% input and its estimated
+    /// gamma instead of building a profile
+    #[arg(long, conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output", "dump", "csv", "json"])]
+    preview: bool,
+
+    /// Compare the curves with those of a second curve file, printing the largest and mean
+    /// difference of each channel and where the largest one is, instead of building a profile.
+    /// "srgb" compares with the sRGB transfer function instead, use ./srgb for a file of that name
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output", "dump", "csv", "json", "preview"])]
+    compare: Option<PathBuf>,
+
+    /// Also print a table of the first, last and mean value of each curve and its output at the
+    /// middle input, sample 128 of 256, before building the profile. Left out with --quiet
+    #[arg(long, conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb"])]
+    stats: bool,
+
+    /// Report the samples of a GIMP curve file that are outside of the 0-1 range, which are
+    /// otherwise clamped silently, instead of building a profile. Exits with an error when any
+    /// are found
+    #[arg(
+        long,
+        conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output", "dump", "preview", "csv", "json"]
+    )]
+    check: bool,
+
+    /// Write the parsed curves to a CSV file with index,r,g,b columns instead of building a
+    /// profile, or to stdout with "-"
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output", "dump"])]
+    csv: Option<PathBuf>,
+
+    /// Write the curves as they are saved in the input file to a JSON file instead of building a
+    /// profile, or to stdout with "-". The object has "gray", "r", "g", "b" and "alpha" arrays,
+    /// and a "linear" flag. None of the curve transformation options are applied
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output", "dump", "csv"])]
+    json: Option<PathBuf>,
+
+    /// Write the red, green and blue curves as a .cube 3D LUT for video software instead of
+    /// building a profile, or to stdout with "-"
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output", "dump", "csv", "json", "preview", "check", "grayscale"]
+    )]
+    cube: Option<PathBuf>,
+
+    /// Amount of points along each axis of the --cube LUT
+    #[arg(long, default_value_t = 33, value_parser = clap::value_parser!(u32).range(2..=256), requires = "cube")]
+    cube_size: u32,
+
+    /// Write a PNG image of a black to white gradient above the same gradient with the curves
+    /// applied instead of building a profile
+    #[cfg(feature = "palette-preview")]
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "icc_output", "dump", "csv", "json", "preview", "check", "cube"]
+    )]
+    palette_preview: Option<PathBuf>,
+
+    /// Leave out the header row of the CSV file
+    #[arg(long, requires = "csv")]
+    no_header: bool,
+
+    /// Convert every .txt, .acv and .dtstyle curve file in a directory, also when gzip
+    /// compressed, to an .icc profile, or .icm with --icm, next to it. A progress bar is shown
+    /// on stderr when it is a terminal, except with --quiet
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["curves_input", "icc_output"])]
+    batch: Option<PathBuf>,
+
+    /// Average the curves of several curve files, e.g. from repeated measurement runs, into a
+    /// single profile. Each file is parsed and transformed on its own, and resampled to --samples
+    /// first when it is given. The files are separated by commas, e.g. --merge-files run1.txt,run2.txt
+    #[arg(long, value_name = "FILES", value_delimiter = ',', conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "compare", "dump", "csv", "json", "cube", "preview", "check"])]
+    merge_files: Vec<PathBuf>,
+
+    /// Read the curve file contents from this base64 text instead of a file, e.g. to pass a
+    /// curve through an environment variable. The profile is written to --output. Line breaks in the text are ignored, and gzip compressed contents are decompressed
+    #[arg(long, value_name = "DATA", conflicts_with_all = ["batch", "gamma", "identity", "reset_srgb", "merge_files", "compare", "dump", "csv", "json", "cube", "preview", "check"])]
+    curve_base64: Option<String>,
+
+    /// Build power function curves with this gamma instead of reading a curve file
+    #[arg(long, conflicts_with = "batch")]
+    gamma: Option<f64>,
+
+    /// Build identity curves that map every value to itself instead of reading a curve file,
+    /// for clearing a previously loaded video card gamma table
+    #[arg(long, conflicts_with_all = ["batch", "gamma"])]
+    identity: bool,
 
-    /// Output file name
-    #[arg(default_value = "out.icc")]
-    icc_output: PathBuf,
+    /// Build curves that decode sRGB instead of reading a curve file, for restoring the
+    /// standard sRGB gamma after a bad profile
+    #[arg(long, conflicts_with_all = ["batch", "gamma", "identity"])]
+    reset_srgb: bool,
 
-    /// Description or name that will appear in Windows' colour management menu
+    /// Format of the input file, detected from the file extension or contents by default
+    #[arg(short, long, value_enum)]
+    format: Option<InputFormat>,
+
+    /// Which block of curves to use, counting from 0, when a GIMP curve file holds several
+    /// complete sets of curves. Other formats hold a single set and ignore it
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    curve_index: usize,
+
+    /// Drop a run of samples of 1 past the declared sample count of a curve, which GIMP
+    /// sometimes pads curves with, instead of failing on the count
+    #[arg(long)]
+    truncate_trailing_padding: bool,
+
+    /// Write the curves as a video card gamma table, as tone curves for colour managed
+    /// applications, or as both. With both, colour managed applications apply the curves a
+    /// second time on top of the loaded gamma table
+    #[arg(short, long, value_enum, default_value_t = CurveMode::Vcgt)]
+    mode: CurveMode,
+
+    /// Amount of entries in the tone curve tables. The curve is linearly interpolated from the
+    /// parsed samples, keeping the endpoints as they are. Parsed curves keep their own amount of
+    /// samples by default, and generated ones have 256. Note that lcms2 always saves VCGT
+    /// tables with 256 entries, so this mostly affects the TRC mode
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..=65530))]
+    samples: Option<u32>,
+
+    /// Use the red, green and blue curves as they are, without applying the value curve after
+    /// each of them. Changes made only in GIMP's Value channel then have no effect, so only use
+    /// this when the value curve is already baked into the colour curves
+    #[arg(long)]
+    no_gray: bool,
+
+    /// Leave the value curve out of only some of the channels, any of r, g and b, e.g. "b" to
+    /// apply it to the red and green curves only
     #[arg(
-        short,
-        long = "description",
-        default_value = "Custom gamma ICC profile"
+        long,
+        value_name = "CHANNELS",
+        value_parser = parse_channel_selection,
+        conflicts_with = "no_gray"
     )]
-    description: String,
+    no_gray_channels: Option<[bool; 3]>,
+
+    /// Build a grayscale profile with the value curve as its only tone curve instead of an sRGB
+    /// profile. The colour curves are ignored, and as grayscale profiles have no video card
+    /// gamma table the curve is only applied by colour managed applications. Curve files with
+    /// only a value curve always give a grayscale profile
+    #[arg(long, conflicts_with_all = [
+        "gamma", "identity", "reset_srgb", "mode", "alpha", "no_gray", "no_gray_channels", "base_profile", "gamma_r", "gamma_g", "gamma_b", "dump", "csv", "json"
+    ])]
+    grayscale: bool,
+
+    /// Carry the alpha curve through to a private tag in the profile
+    #[arg(long)]
+    alpha: bool,
+
+    /// Gamma applied to the red curve after parsing, output = input ^ gamma
+    #[arg(long, default_value_t = 1.0)]
+    gamma_r: f32,
+
+    /// Gamma applied to the green curve after parsing, output = input ^ gamma
+    #[arg(long, default_value_t = 1.0)]
+    gamma_g: f32,
+
+    /// Gamma applied to the blue curve after parsing, output = input ^ gamma
+    #[arg(long, default_value_t = 1.0)]
+    gamma_b: f32,
+
+    /// Channels that the curves are applied to, any of r, g and b, e.g. "b" to only change the
+    /// blue channel. The other channels are written as identity curves
+    #[arg(
+        long,
+        value_name = "CHANNELS",
+        default_value = "rgb",
+        value_parser = parse_channel_selection,
+        conflicts_with = "grayscale"
+    )]
+    channels: [bool; 3],
+
+    /// Move the first and last samples of each curve to exactly 0 and 65535 when rounding has
+    /// left them slightly off, as some displays reject gamma tables that don't span the full range
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    snap_endpoints: bool,
+
+    /// Rescale the red, green and blue curves so that each spans the full output range, its
+    /// lowest sample mapped to 0 and its highest to 65535, to restore contrast to flat curves
+    #[arg(long)]
+    autolevel: bool,
+
+    /// Invert the red, green and blue curves, e.g. to undo the effect of another profile
+    #[arg(long)]
+    invert: bool,
+
+    /// Smooth each curve with a moving average over WINDOW samples to reduce banding from
+    /// coarse curves. The endpoints are kept as they are
+    #[arg(long, value_name = "WINDOW", value_parser = clap::value_parser!(u32).range(1..))]
+    smooth: Option<u32>,
+
+    /// Apply each curve N times in a row, composing it with itself, to push its effect further
+    /// for testing. 1 uses the curve as it is
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=10))]
+    repeat: Option<u32>,
+
+    /// Name the output out.icm, and the profiles written in batch mode .icm, as Windows often
+    /// names profiles. The contents are the same as with .icc, which is the same format
+    #[arg(long)]
+    icm: bool,
+
+    /// Overwrite the output profile if it already exists
+    #[arg(long)]
+    force: bool,
+
+    /// Don't log informational messages such as the files being read and written. Warnings
+    /// and errors are still printed. The RUST_LOG environment variable takes precedence
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also log the amount of samples and the minimum, maximum and mean value of each channel
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Exit with an error instead of a warning when a curve fails a validation check, such as
+    /// decreasing somewhere
+    #[arg(long)]
+    strict: bool,
+
+    /// Exit with an error instead of converting the curve to sRGB when it is saved in linear light
+    #[arg(long)]
+    linear_fail: bool,
+
+    /// Don't record the name and a hash of the curve file in a private tag of the profile
+    #[arg(long)]
+    no_provenance: bool,
+
+    /// Print the SHA-256 hash of the profile to stderr, as sha256sum would print it for the
+    /// saved file. Also works with --dry-run, and with --date or SOURCE_DATE_EPOCH the hash is
+    /// the same for every build of the same curves
+    #[arg(long)]
+    print_sha256: bool,
+
+    /// Don't log the note about Windows needing a loader for the gamma table after saving
+    #[arg(long)]
+    no_loader_note: bool,
+
+    /// Print how to make Windows load the gamma table of the profile at login, and exit
+    #[arg(long, exclusive = true)]
+    print_loader_hint: bool,
+
+    /// Fail when the estimated gamma of a red, green or blue curve, or the --gamma, is below
+    /// this, as a safety rail against shipping an extreme curve
+    #[arg(long, value_name = "GAMMA", default_value_t = 0.1)]
+    min_gamma: f32,
+
+    /// Fail when the estimated gamma of a red, green or blue curve, or the --gamma, is above
+    /// this, e.g. to catch a curve that would black out the screen
+    #[arg(long, value_name = "GAMMA", default_value_t = 10.0)]
+    max_gamma: f32,
+
+    /// Warn when a curve maps more than PERCENT of the input range to a single output value,
+    /// which posterizes those tones
+    #[arg(long, value_name = "PERCENT", default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..=100))]
+    plateau_threshold: u32,
+
+    /// Accept curves that decrease throughout, as with an intentionally inverted curve, instead
+    /// of reporting them as not monotonic. Curves that both rise and fall are still reported
+    #[arg(long)]
+    allow_descending: bool,
+
+    /// Parse, validate and build the profile in memory without writing it, exiting with an error
+    /// on any problem. Useful for checking curve files, also in batch mode. Combine with --strict
+    /// to also fail on validation warnings
+    #[arg(long, conflicts_with = "icc_output")]
+    dry_run: bool,
+
+    /// TOML file with defaults for the profile metadata and curve options, or JSON when the
+    /// name ends in .json. Options given on the command line take precedence over the file
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+/// Defaults read from a --config file. The keys are named like the command line options they
+/// stand in for, e.g. `icc-version = "2"` for --icc-version 2
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    description: Option<String>,
+    copyright: Option<String>,
+    manufacturer: Option<String>,
+    model: Option<String>,
+    locale: Option<String>,
+    icc_version: Option<String>,
+    bit_depth: Option<String>,
+    intent: Option<String>,
+    mode: Option<String>,
+    samples: Option<u32>,
+}
+
+impl Config {
+    /// Argument ids and values of the options set in the file
+    fn options(&self) -> Vec<(&'static str, String)> {
+        let text = [
+            ("description", &self.description),
+            ("copyright", &self.copyright),
+            ("manufacturer", &self.manufacturer),
+            ("model", &self.model),
+            ("locale", &self.locale),
+            ("icc_version", &self.icc_version),
+            ("bit_depth", &self.bit_depth),
+            ("intent", &self.intent),
+            ("mode", &self.mode),
+        ];
+        let mut options: Vec<_> = text
+            .into_iter()
+            .filter_map(|(id, value)| Some((id, value.clone()?)))
+            .collect();
+        options.extend(self.samples.map(|samples| ("samples", samples.to_string())));
+        options
+    }
+}
+
+/// Parses the contents of a --config file, as JSON when its name ends in .json and as TOML
+/// otherwise
+fn parse_config(text: &str, path: &Path) -> Result<Config, String> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::from_str(text).map_err(|err| err.to_string())
+    } else {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+}
+
+/// Parses the command line and fills in the options of the config file as defaults. An option
+/// is left out when the command line gives it, or gives an option that it conflicts with, such
+/// as --gamma for `bit-depth`, so that the file never makes the command line invalid
+fn merge_config(argv: Vec<OsString>, config: &Config) -> Result<Args, clap::Error> {
+    let command = Args::command();
+    let matches = command.clone().try_get_matches_from(argv)?;
+    let mut args = Args::from_arg_matches(&matches)?;
+
+    let given: Vec<&Arg> = command
+        .get_arguments()
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .collect();
+    let conflict = |a: &Arg, b: &Arg| {
+        a.get_id() == b.get_id()
+            || command
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|other| other.get_id() == b.get_id())
+    };
+    for (id, value) in config.options() {
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .expect("config option without an argument");
+        if given
+            .iter()
+            .any(|other| conflict(arg, other) || conflict(other, arg))
+        {
+            continue;
+        }
+        args.set_config_option(id, &value).map_err(|err| {
+            command.clone().error(
+                ErrorKind::InvalidValue,
+                format!("invalid value {value:?} for {id} in the config file: {err}"),
+            )
+        })?;
+    }
+    Ok(args)
+}
+
+/// Applies the --config file, if any, to the parsed arguments
+fn apply_config(args: Args) -> Args {
+    let Some(path) = &args.config else {
+        return args;
+    };
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        fail(
+            ErrorCategory::Io,
+            format!("Failed to read config file {path:?}: {err}"),
+        )
+    });
+    let config = parse_config(&text, path).unwrap_or_else(|err| {
+        fail(
+            ErrorCategory::Parse,
+            format!("Invalid config file {path:?}: {err}"),
+        )
+    });
+    merge_config(env::args_os().collect(), &config).unwrap_or_else(|err| err.exit())
+}
+
+/// Parses a panel bit depth for --target-bits
+fn parse_target_bits(text: &str) -> Result<u8, String> {
+    match text.parse() {
+        Ok(bits @ (8 | 10 | 12 | 16)) => Ok(bits),
+        _ => Err("expected 8, 10, 12 or 16".to_string()),
+    }
+}
+
+/// Parses a locale like en-US or en_US into a 2-letter language and country code
+fn parse_locale(text: &str) -> Result<Locale, String> {
+    match text.split_once(['-', '_']) {
+        Some((language, country))
+            if language.len() == 2
+                && country.len() == 2
+                && text
+                    .chars()
+                    .all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_') =>
+        {
+            Ok(Locale::new(&format!(
+                "{}_{}",
+                language.to_ascii_lowercase(),
+                country.to_ascii_uppercase()
+            )))
+        }
+        _ => Err("expected a language and country code such as en-US".to_string()),
+    }
+}
+
+/// Parses a selection of channels like "rb" into whether each of red, green and blue is selected
+fn parse_channel_selection(text: &str) -> Result<[bool; 3], String> {
+    let mut selected = [false; 3];
+    for c in text.chars() {
+        let index = match c.to_ascii_lowercase() {
+            'r' => 0,
+            'g' => 1,
+            'b' => 2,
+            _ => return Err(format!("unknown channel {c:?}, expected r, g or b")),
+        };
+        if selected[index] {
+            return Err(format!("channel {c:?} is given more than once"));
+        }
+        selected[index] = true;
+    }
+    if text.is_empty() {
+        return Err("expected at least one of r, g and b".to_string());
+    }
+    Ok(selected)
+}
+
+/// Parses comma separated CIE xy chromaticities, checking that each is a possible colour
+fn parse_chromaticities(text: &str, count: usize) -> Result<Vec<(f64, f64)>, String> {
+    let values = text
+        .split(',')
+        .map(|value| value.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|err| format!("{err} in {text:?}"))?;
+    if values.len() != count * 2 {
+        return Err(format!(
+            "expected {} comma separated numbers, found {}",
+            count * 2,
+            values.len()
+        ));
+    }
+    let xy: Vec<(f64, f64)> = values.chunks(2).map(|xy| (xy[0], xy[1])).collect();
+    if let Some((x, y)) = xy
+        .iter()
+        .find(|&&(x, y)| x < 0.0 || y <= 0.0 || x + y > 1.0)
+    {
+        return Err(format!("{x},{y} is not a valid CIE xy chromaticity"));
+    }
+    Ok(xy)
+}
+
+/// Parses a white point name like d65 or a CIE xy chromaticity like 0.3127,0.329
+fn parse_white_point(text: &str) -> Result<(f64, f64), String> {
+    match text.to_ascii_lowercase().as_str() {
+        "d65" => Ok(D65),
+        "d50" => Ok(D50),
+        _ => Ok(parse_chromaticities(text, 1)?[0]),
+    }
+}
+
+/// Parses the name of a colour space like p3 or the red, green and blue CIE xy chromaticities of
+/// its primaries
+fn parse_primaries(text: &str) -> Result<[(f64, f64); 3], String> {
+    let primaries = match text.to_ascii_lowercase().as_str() {
+        "srgb" => SRGB_PRIMARIES,
+        "p3" => [(0.68, 0.32), (0.265, 0.69), (0.15, 0.06)],
+        "adobe" => [(0.64, 0.33), (0.21, 0.71), (0.15, 0.06)],
+        "rec2020" => [(0.708, 0.292), (0.17, 0.797), (0.131, 0.046)],
+        _ => parse_chromaticities(text, 3)?.try_into().unwrap(),
+    };
+    let [(rx, ry), (gx, gy), (bx, by)] = primaries;
+    // primaries on a line can't be turned into a colorant matrix
+    if ((rx - bx) * (gy - by) - (gx - bx) * (ry - by)).abs() < 1e-6 {
+        return Err("the primaries lie on a line".to_string());
+    }
+    Ok(primaries)
+}
+
+/// Parses a translation like de-DE=MeinProfil
+fn parse_localized_text(text: &str) -> Result<(Locale, String), String> {
+    let (locale, text) = text
+        .split_once('=')
+        .ok_or("expected a locale and text such as de-DE=MeinProfil")?;
+    Ok((parse_locale(locale)?, text.to_string()))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check that an existing profile has a VCGT tag, printing the amount of channels and a few
+    /// sample values of each
+    Verify {
+        /// Profile to check
+        icc: PathBuf,
+    },
+    /// Build a profile from a built-in sample curve and read it back, printing PASS when the
+    /// curves survive the round trip through lcms2
+    Selftest,
+    /// Apply the curves of a curve file to the pixels of an image, writing the result as a new
+    /// PNG image. 8 and 16-bit images keep their bit depth
+    #[cfg(feature = "apply-image")]
+    Apply {
+        /// Curve file to read the curves from
+        curves: PathBuf,
+        /// PNG image to apply the curves to
+        image: PathBuf,
+        /// Output PNG image
+        output: PathBuf,
+    },
+}
+
+impl Args {
+    /// Sets the option with the given argument id to a value from the --config file, checking
+    /// it the same way as on the command line
+    fn set_config_option(&mut self, id: &str, value: &str) -> Result<(), String> {
+        match id {
+            "description" => self.description = Some(value.to_string()),
+            "copyright" => self.copyright = Some(value.to_string()),
+            "manufacturer" => self.manufacturer = Some(value.to_string()),
+            "model" => self.model = Some(value.to_string()),
+            "locale" => self.locale = Some(parse_locale(value)?),
+            "icc_version" => self.icc_version = Some(IccVersion::from_str(value, true)?),
+            "bit_depth" => self.bit_depth = BitDepth::from_str(value, true)?,
+            "intent" => self.intent = Some(RenderingIntent::from_str(value, true)?),
+            "mode" => self.mode = CurveMode::from_str(value, true)?,
+            "samples" => {
+                self.samples = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|samples| (2..=65530).contains(samples))
+                        .ok_or("expected a number from 2 to 65530")?,
+                )
+            }
+            _ => unreachable!("unknown config option {id}"),
+        }
+        Ok(())
+    }
+
+    /// Description of a profile made from the given curve file: --description, or otherwise
+    /// the file name without its extension so that profiles can be told apart
+    fn description_for(&self, input: &Path) -> String {
+        if let Some(description) = &self.description {
+            return description.clone();
+        }
+        match without_gz(input).file_stem() {
+            Some(stem) if input.as_os_str() != "-" => stem.to_string_lossy().into_owned(),
+            _ => DEFAULT_DESCRIPTION.to_string(),
+        }
+    }
+
+    /// Options for building the profile with the given description
+    fn profile_options(&self, description: &str) -> ProfileOptions {
+        ProfileOptions {
+            description: description.to_string(),
+            mode: self.mode,
+            bit_depth: self.bit_depth,
+            intent: self.intent,
+            copyright: self.copyright.clone(),
+            manufacturer: self.manufacturer.clone(),
+            model: self.model.clone(),
+            creation_date: self.creation_date(),
+            version: self.icc_version,
+            base_profile: self.base_profile(),
+            locale: self.locale.unwrap_or_else(Locale::none),
+            localized_descriptions: self.description_locale.clone(),
+            source: None,
+            merge_vcgt: self.merge_vcgt,
+            white_point: self.white_point,
+            primaries: self.primaries,
+            target_bits: self.target_bits,
+        }
+    }
+
+    /// Contents of the --base-profile file, checked to be a profile lcms2 can read
+    fn base_profile(&self) -> Option<Vec<u8>> {
+        let path = self.base_profile.as_ref()?;
+        let bytes = fs::read(path).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Could not read base profile {path:?}: {err}"),
+            )
+        });
+        if let Err(err) = Profile::new_icc(&bytes) {
+            fail(
+                ErrorCategory::Parse,
+                format!("Could not load base profile {path:?}: {err}"),
+            );
+        }
+        let has_vcgt =
+            Profile::new_icc(&bytes).is_ok_and(|icc| icc.has_tag(lcms2::TagSignature::VcgtTag));
+        if has_vcgt && !self.merge_vcgt && self.mode != CurveMode::Trc {
+            warn!(
+                "base profile {path:?} already has a VCGT tag, it is replaced. Use --merge-vcgt to apply the curves on top of it instead"
+            );
+        }
+        Some(bytes)
+    }
+
+    /// Creation date from --date or SOURCE_DATE_EPOCH for reproducible builds
+    fn creation_date(&self) -> Option<CreationDate> {
+        self.date.or_else(|| {
+            let epoch = env::var("SOURCE_DATE_EPOCH").ok()?;
+            let Ok(timestamp) = epoch.parse() else {
+                warn!("ignoring invalid SOURCE_DATE_EPOCH {epoch:?}");
+                return None;
+            };
+            match CreationDate::from_unix_timestamp(timestamp) {
+                Ok(date) => Some(date),
+                Err(err) => {
+                    warn!("ignoring SOURCE_DATE_EPOCH: {err}");
+                    None
+                }
+            }
+        })
+    }
+
+    /// Amount of entries in curves that are generated instead of parsed, see --samples
+    fn generated_samples(&self) -> usize {
+        self.samples.unwrap_or(DEFAULT_SAMPLES) as usize
+    }
+
+    /// Path the profile is written to, from --output or the output file argument
+    fn output_path(&self) -> PathBuf {
+        let output = self.output.as_ref().or(self.icc_output.as_ref());
+        output.cloned().unwrap_or_else(|| {
+            PathBuf::from(if self.icm {
+                DEFAULT_ICM_OUTPUT
+            } else {
+                DEFAULT_OUTPUT
+            })
+        })
+    }
+
+    /// Extension of the profiles written in batch mode
+    fn output_extension(&self) -> &'static str {
+        if self.icm {
+            "icm"
+        } else {
+            "icc"
+        }
+    }
+}
+
+/// Errors that can happen while converting curve file contents to a profile
+#[derive(Debug)]
+enum ConvertError {
+    Parse(CurveParseError),
+    /// A curve failed a validation check with --strict
+    Validation(String),
+    /// A file could not be read or written
+    Io(io::Error),
+    /// lcms2 failed to save the profile
+    Save(lcms2::Error),
+    /// The profile could not be created from the curves, base profile, white point or primaries
+    Build(ProfileError),
+}
+
+impl ConvertError {
+    fn category(&self) -> ErrorCategory {
+        match self {
+            ConvertError::Parse(_) => ErrorCategory::Parse,
+            ConvertError::Validation(_) | ConvertError::Build(_) => ErrorCategory::Validation,
+            ConvertError::Io(_) | ConvertError::Save(_) => ErrorCategory::Io,
+        }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::Parse(err) => write!(f, "could not parse curves: {err}"),
+            ConvertError::Validation(message) => write!(f, "{message}"),
+            ConvertError::Io(err) => write!(f, "{err}"),
+            ConvertError::Save(err) => write!(f, "could not save profile: {err}"),
+            ConvertError::Build(err) => write!(f, "could not build profile: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+impl From<CurveParseError> for ConvertError {
+    fn from(err: CurveParseError) -> Self {
+        ConvertError::Parse(err)
+    }
+}
+
+impl From<io::Error> for ConvertError {
+    fn from(err: io::Error) -> Self {
+        ConvertError::Io(err)
+    }
+}
+
+impl From<lcms2::Error> for ConvertError {
+    fn from(err: lcms2::Error) -> Self {
+        ConvertError::Save(err)
+    }
+}
+
+/// Kind of failure, which decides the exit code so that scripts can tell them apart:
+///
+/// | Code | Meaning                                                                      |
+/// |------|------------------------------------------------------------------------------|
+/// | 0    | Success                                                                      |
+/// | 1    | Other failures: verify found no VCGT tag, selftest failed                    |
+/// | 2    | An input file could not be parsed, or the command line arguments are invalid |
+/// | 3    | A file could not be read or written, or the output exists without --force    |
+/// | 4    | A validation check failed, with --strict, --check or the gamma bounds        |
+///
+/// Batch mode exits with the code shared by all failed files, or 1 if they failed differently
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ErrorCategory {
+    Other,
+    Parse,
+    Io,
+    Validation,
+}
+
+impl ErrorCategory {
+    fn exit_code(self) -> i32 {
+        match self {
+            ErrorCategory::Other => 1,
+            // the same code clap exits with for invalid arguments
+            ErrorCategory::Parse => 2,
+            ErrorCategory::Io => 3,
+            ErrorCategory::Validation => 4,
+        }
+    }
+}
+
+/// Exit code of batch mode, see [ErrorCategory]
+fn batch_exit_code(failures: &[ErrorCategory]) -> i32 {
+    match failures.split_first() {
+        None => 0,
+        Some((first, rest)) if rest.iter().all(|category| category == first) => first.exit_code(),
+        Some(_) => ErrorCategory::Other.exit_code(),
+    }
+}
+
+/// Prints an error message and exits with the code of its category
+fn fail(category: ErrorCategory, message: impl fmt::Display) -> ! {
+    eprintln!("{message}");
+    process::exit(category.exit_code())
+}
+
+/// Reports a failed validation check as a warning, or as an error with --strict
+fn validation_failed(message: String, args: &Args) -> Result<(), ConvertError> {
+    if args.strict {
+        return Err(ConvertError::Validation(message));
+    }
+    warn!("{message}");
+    Ok(())
+}
+
+/// Checks the gamma of a curve against --min-gamma and --max-gamma. Unlike other validation
+/// checks this always fails, also without --strict
+fn check_gamma_bounds(name: &str, gamma: f32, args: &Args) -> Result<(), ConvertError> {
+    if (args.min_gamma..=args.max_gamma).contains(&gamma) {
+        return Ok(());
+    }
+    Err(ConvertError::Validation(format!(
+        "{name} has a gamma of {gamma:.2}, outside of the allowed {} to {}",
+        args.min_gamma, args.max_gamma
+    )))
+}
+
+/// Parses curve file contents and applies the transformations given in the arguments, giving
+/// the curves that end up in the profile
+fn prepare_curves(input: &[u8], path: &Path, args: &Args) -> Result<Vec<Vec<u16>>, ConvertError> {
+    let options = ParseOptions {
+        linear_fail: args.linear_fail,
+        include_alpha: args.alpha,
+        skip_gray: args.no_gray,
+        skip_gray_channels: args.no_gray_channels.unwrap_or_default(),
+        curve_index: args.curve_index,
+        truncate_trailing_padding: args.truncate_trailing_padding,
+    };
+    let grayscale = args.grayscale || is_gray_only(path, input, args.format);
+    if grayscale && !args.grayscale {
+        info!("the file only has a value curve, building a grayscale profile");
+    }
+    let mut curves = if grayscale {
+        vec![parse_gray_curve(path, input, args.format, &options)?]
+    } else {
+        detect_and_parse(path, input, args.format, &options)?
+    };
+    let names = curve_names(&curves);
+
+    if args.autolevel {
+        for curve in curves.iter_mut().take(3) {
+            *curve = transform::stretch_range(curve);
+        }
+    }
+
+    if args.snap_endpoints {
+        for (name, curve) in names.iter().zip(&mut curves) {
+            let snapped = transform::snap_endpoints(curve);
+            if snapped != *curve {
+                warn!("channel {name} did not span the full range and its endpoints were snapped to 0 and 65535");
+                *curve = snapped;
+            }
+        }
+    }
+
+    if let Some(times) = args.repeat {
+        for curve in curves.iter_mut().take(3) {
+            *curve = transform::repeat_curve(curve, times as usize);
+        }
+    }
+
+    for (curve, gamma) in curves
+        .iter_mut()
+        .zip([args.gamma_r, args.gamma_g, args.gamma_b])
+    {
+        *curve = transform::apply_gamma(curve, gamma);
+    }
+
+    if args.invert {
+        for curve in curves.iter_mut().take(3) {
+            *curve = transform::invert_curve(curve);
+        }
+    }
+
+    if let Some(window) = args.smooth {
+        for curve in &mut curves {
+            *curve = transform::smooth_curve(curve, window as usize);
+        }
+    }
+
+    if let (true, Some(bits)) = (args.quantize, args.target_bits) {
+        for curve in curves.iter_mut().take(3) {
+            *curve = transform::quantize_curve(curve, bits);
+        }
+    }
+
+    for (name, curve) in names.iter().zip(&curves) {
+        let descending = args.allow_descending && validate::check_descending(curve).is_ok();
+        if let (Err(index), false) = (validate::check_monotonic(curve), descending) {
+            validation_failed(
+                format!("channel {name} is not monotonic, it decreases at sample {index}"),
+                args,
+            )?;
+        }
+        if !descending && *name != CHANNEL_NAMES[3] {
+            let gamma = analysis::estimate_gamma(curve);
+            check_gamma_bounds(&format!("channel {name}"), gamma, args)?;
+        }
+        // an identity alpha curve is the usual case, and alpha has no visible effect anyway
+        if *name != CHANNEL_NAMES[3] && validate::is_identity(curve) {
+            warn!("channel {name} is an identity curve; profile will have no visible effect");
+        }
+        let plateau = validate::longest_plateau(curve);
+        let last = curve.len().saturating_sub(1).max(1) as f64;
+        let (from, to) = (
+            plateau.start as f64 / last * 100.0,
+            (plateau.end - 1) as f64 / last * 100.0,
+        );
+        if to - from > args.plateau_threshold as f64 {
+            warn!(
+                "channel {name} maps inputs from {from:.0}% to {to:.0}% all to {}, which posterizes those tones",
+                curve[plateau.start]
+            );
+        }
+        if log::log_enabled!(Level::Debug) {
+            let min = curve.iter().min().unwrap();
+            let max = curve.iter().max().unwrap();
+            let mean = curve.iter().map(|&value| value as f64).sum::<f64>() / curve.len() as f64;
+            debug!(
+                "channel {name}: {} samples, min {min}, max {max}, mean {mean:.0}",
+                curve.len()
+            );
+        }
+    }
+
+    // deselected channels are replaced only now so that they aren't reported as identity curves
+    for (curve, selected) in curves.iter_mut().zip(args.channels) {
+        if !selected && !grayscale {
+            *curve = transform::identity_curve(curve.len());
+        }
+    }
+
+    if let Some(samples) = args.samples {
+        for curve in curves.iter_mut() {
+            *curve = transform::resample_curve(curve, samples as usize);
+        }
+    }
+    Ok(curves)
+}
+
+/// Parses curve file contents and builds a profile from them according to the arguments
+fn convert(
+    input: &[u8],
+    path: &Path,
+    description: &str,
+    args: &Args,
+) -> Result<Profile, ConvertError> {
+    let curves = prepare_curves(input, path, args)?;
+    build_prepared(&curves, provenance(input, path), description, args)
+}
+
+/// Names of the curves returned by [prepare_curves], which are a single gray curve for a
+/// grayscale profile
+fn curve_names(curves: &[Vec<u16>]) -> &'static [&'static str] {
+    if curves.len() == 1 {
+        &["gray"]
+    } else {
+        &CHANNEL_NAMES
+    }
+}
+
+/// Builds a profile from curves returned by [prepare_curves], with `source` as its provenance
+fn build_prepared(
+    curves: &[Vec<u16>],
+    source: String,
+    description: &str,
+    args: &Args,
+) -> Result<Profile, ConvertError> {
+    if args.stats && !args.quiet {
+        print_stats(curves, args);
+    }
+    let mut options = args.profile_options(description);
+    if !args.no_provenance {
+        options.source = Some(source);
+    }
+    if curves.len() == 1 {
+        build_grayscale_profile(&curves[0], &options)
+    } else {
+        build_profile_with_options(curves, &options)
+    }
+    .map_err(ConvertError::Build)
+}
+
+/// Exits with the error of a profile built directly from the arguments, without a curve file
+fn built(profile: Result<Profile, ProfileError>) -> Profile {
+    profile.unwrap_or_else(|err| {
+        let message = format!("Could not build profile: {err}");
+        fail(ConvertError::Build(err).category(), message)
+    })
+}
+
+/// Prints the --stats table of the curves as 16-bit values, to stderr if the profile is written
+/// to stdout
+fn print_stats(curves: &[Vec<u16>], args: &Args) {
+    let names = curve_names(curves);
+    let mut table = format!(
+        "{:<8}{:>8}{:>8}{:>8}{:>8}\n",
+        "channel", "first", "last", "mean", "middle"
+    );
+    for (name, curve) in names.iter().zip(curves) {
+        let mean = curve.iter().map(|&value| value as f64).sum::<f64>() / curve.len() as f64;
+        table += &format!(
+            "{name:<8}{:>8}{:>8}{mean:>8.0}{:>8}\n",
+            curve[0],
+            curve[curve.len() - 1],
+            curve[curve.len() / 2]
+        );
+    }
+
+    if args.output_path().as_os_str() == "-" {
+        eprint!("{table}");
+    } else {
+        print!("{table}");
+    }
+}
+
+/// Describes a curve file by its name and the SHA-256 hash of its contents, for tracing a
+/// profile back to the file it was made from
+fn provenance(input: &[u8], path: &Path) -> String {
+    let name = if path.as_os_str() == "-" {
+        "stdin".into()
+    } else {
+        path.file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+    };
+    format!("{name}\nsha256:{}", sha256_hex(input))
+}
+
+/// SHA-256 hash of the bytes as lowercase hex
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Prints the SHA-256 hash of the serialized profile to stderr for --print-sha256, in the
+/// format of sha256sum so that the saved file can be checked with `sha256sum -c`
+fn print_profile_hash(icc: &Profile, output: &Path) -> Result<(), lcms2::Error> {
+    let bytes = profile_to_bytes(icc)?;
+    eprintln!("{}  {}", sha256_hex(&bytes), output.display());
+    Ok(())
+}
+
+/// Reads the curve file named on the command line, or stdin for "-", decompressing it if it is
+/// gzip compressed
+fn read_input(curves_input: &Path) -> Vec<u8> {
+    // curves are exported from GIMP curve tool
+    let input = if curves_input.as_os_str() == "-" {
+        info!("reading curve samples from stdin...");
+        let mut input = Vec::new();
+        io::stdin()
+            .read_to_end(&mut input)
+            .unwrap_or_else(|err| fail(ErrorCategory::Io, format!("Could not read stdin: {err}")));
+        input
+    } else {
+        info!("reading curve samples from {:?}...", curves_input);
+        fs::read(curves_input).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Could not read file {:?}: {}", curves_input, err),
+            )
+        })
+    };
+    decompress_gzip(input).unwrap_or_else(|err| {
+        fail(
+            ErrorCategory::Io,
+            format!("Could not decompress {:?}: {err}", curves_input),
+        )
+    })
+}
+
+/// The path without a .gz extension, for naming what is made from a compressed curve file
+fn without_gz(path: &Path) -> PathBuf {
+    match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("gz") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Reads the curve file named on the command line and converts it to a profile
+fn convert_input(curves_input: &Path, args: &Args) -> Profile {
+    let input = read_input(curves_input);
+    let description = args.description_for(curves_input);
+    convert(&input, curves_input, &description, args).unwrap_or_else(|err| {
+        fail(
+            err.category(),
+            format!("Could not convert {:?}: {err}", curves_input),
+        )
+    })
+}
+
+/// Decodes the curve file contents given with --curve-base64, ignoring whitespace such as the
+/// line breaks of wrapped base64 output
+fn decode_curve_base64(data: &str) -> Result<Vec<u8>, String> {
+    let data: String = data.split_whitespace().collect();
+    let input = STANDARD
+        .decode(data)
+        .map_err(|err| format!("Invalid --curve-base64 data: {err}"))?;
+    decompress_gzip(input).map_err(|err| format!("Could not decompress --curve-base64 data: {err}"))
 }
 
-/// Parses e.g. "0.0 0.001 0.033 ..." to vec of numbers scaled from 0 to 65535
-fn parse_u16_curve_vec(input: &str) -> Vec<u16> {
-    return input
-        .split(" ")
-        .map(|it| it.parse::<f32>().expect("failed to parse number"))
-        .map(|f| (f * (u16::MAX) as f32).round() as u16)
+/// Converts the curve file contents given with --curve-base64 to a profile
+fn convert_base64(data: &str, args: &Args) -> Profile {
+    info!("decoding curve samples from --curve-base64...");
+    let input = decode_curve_base64(data).unwrap_or_else(|err| fail(ErrorCategory::Parse, err));
+    let description = args.description.as_deref().unwrap_or(DEFAULT_DESCRIPTION);
+    convert(&input, Path::new("base64"), description, args).unwrap_or_else(|err| {
+        fail(
+            err.category(),
+            format!("Could not convert --curve-base64 data: {err}"),
+        )
+    })
+}
+
+/// Reads and prepares each of the --merge-files and builds a profile from their averaged curves.
+/// Only the channels all files have are kept, so alpha is dropped unless every file has one
+fn convert_merged(files: &[PathBuf], args: &Args) -> Profile {
+    let mut sets = Vec::new();
+    let mut sources = Vec::new();
+    for path in files {
+        let input = read_input(path);
+        let curves = prepare_curves(&input, path, args).unwrap_or_else(|err| {
+            fail(err.category(), format!("Could not convert {path:?}: {err}"))
+        });
+        sets.push(curves);
+        sources.push(provenance(&input, path));
+    }
+
+    let channels = sets.iter().map(Vec::len).min().unwrap_or(0);
+    let curves: Vec<Vec<u16>> = (0..channels)
+        .map(|channel| {
+            let curves: Vec<Vec<u16>> = sets.iter().map(|set| set[channel].clone()).collect();
+            transform::average_curves(&curves)
+        })
         .collect();
+    info!("merged the curves of {} files", files.len());
+
+    let description = args.description.as_deref().unwrap_or(DEFAULT_DESCRIPTION);
+    build_prepared(&curves, sources.join("\n"), description, args)
+        .unwrap_or_else(|err| fail(err.category(), format!("Could not merge curves: {err}")))
 }
 
-/// Scales from 0-65535 to 0-255
-fn scale_u16_to_u8_range(input: u16) -> u8 {
-    ((input as f32 / u16::MAX as f32) * u8::MAX as f32) as u8
+/// Converts a single curve file in batch mode, writing the profile next to it
+fn convert_batch_file(input: &Path, args: &Args) -> Result<PathBuf, ConvertError> {
+    let output = without_gz(input).with_extension(args.output_extension());
+    if output.exists() && !args.force && !args.dry_run {
+        return Err(ConvertError::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{output:?} already exists, pass --force to overwrite it"),
+        )));
+    }
+
+    let contents = decompress_gzip(fs::read(input)?)?;
+    let description = args.description_for(input);
+
+    let mut icc = convert(&contents, input, &description, args)?;
+    if args.print_sha256 {
+        print_profile_hash(&icc, &output)?;
+    }
+    if args.dry_run {
+        check_serializes(&icc)?;
+    } else {
+        icc.save_profile_to_file(&output)?;
+    }
+    Ok(output)
+}
+
+/// Directory a file is written to, the current directory for a bare file name
+fn output_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
 }
 
-/// Parses GIMP's new curve format which is formatted in a LISP-like way
-fn parse_curves(text: String) -> Vec<Vec<u16>> {
-    // gimp seems to be able to save linear curves which will probably look wrong
-    if text.contains("linear yes") {
-        println!("Curve input is saved in linear light. The result might not look correct")
+/// Checks that a directory exists and a file can be created in it, so that a mistyped output
+/// path fails before any curves are parsed
+fn check_writable_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("Output directory {dir:?} does not exist"));
     }
+    let probe = dir.join(format!(".rs-gimp-to-icc-{}.tmp", process::id()));
+    fs::File::create(&probe)
+        .map_err(|err| format!("Output directory {dir:?} is not writable: {err}"))?;
+    fs::remove_file(&probe).ok();
+    Ok(())
+}
 
-    // mR flags: multi-line and CRLF mode
-    let re = Regex::new(r"(?Rm)^ *\(samples \d+ (.*)\)\)$").unwrap();
-    // gets us the values portion of (samples n value1 value2 value3...) in the file
-    let caps: Vec<&str> = re
-        .captures_iter(&text)
-        .map(|it| it.get(1).unwrap().as_str())
+/// Serializes a profile and reads it back for --dry-run, as a stand-in for saving it
+fn check_serializes(icc: &Profile) -> Result<(), lcms2::Error> {
+    Profile::new_icc(&profile_to_bytes(icc)?).map(|_| ())
+}
+
+/// Converts every .txt, .acv and .dtstyle file in a directory, and those files compressed as
+/// .gz, continuing past failures. Returns the categories of the failures
+fn run_batch(dir: &Path, args: &Args) -> Vec<ErrorCategory> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Could not read directory {dir:?}: {err}"),
+            )
+        })
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && InputFormat::from_path(path).is_some())
         .collect();
+    inputs.sort();
 
-    // 1 value curve (gray), and 3 colour curves (R, G, B). Possibly also alpha but that is ignored
-    assert!(
-        caps.len() >= 4,
-        "Could not parse 4 curves from file. Exiting..."
+    // drawn on stderr, which indicatif leaves out when it isn't a terminal
+    let progress = if args.quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(inputs.len() as u64)
+    };
+    progress.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("=> "),
     );
 
-    let gray = parse_u16_curve_vec(caps[0]);
-    // GIMP doesn't seem to save curves of different accuracy
-    assert!(gray.len() == 256);
+    let mut failures = Vec::new();
+    for input in &inputs {
+        progress.set_message(input.file_name().unwrap().to_string_lossy().into_owned());
+        // the bar is cleared while the file is converted so that log messages don't break it
+        progress.suspend(|| match convert_batch_file(input, args) {
+            Ok(_) if args.dry_run => info!("checked {input:?}"),
+            Ok(output) => info!("converted {input:?} to {output:?}"),
+            Err(err) => {
+                eprintln!("failed to convert {input:?}: {err}");
+                failures.push(err.category());
+            }
+        });
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+
+    let verb = if args.dry_run { "checked" } else { "converted" };
+    let failed = failures.len();
+    println!("{verb} {}, failed {failed}", inputs.len() - failed);
+    if failed < inputs.len() {
+        log_loader_note(args);
+    }
+    failures
+}
 
-    let rgb_values = caps[1..4].iter().map(|&list| parse_u16_curve_vec(list));
+/// Writes the parsed curves as text for --dump or --csv instead of building a profile
+fn export_curves(args: &Args) {
+    let to_stdout = args.dump || args.csv.as_ref().is_some_and(|csv| csv.as_os_str() == "-");
+    let curves_input = args.curves_input.as_ref().unwrap();
+    let input = read_input(curves_input);
+    let curves = prepare_curves(&input, curves_input, args).unwrap_or_else(|err| {
+        fail(
+            err.category(),
+            format!("Could not convert {:?}: {err}", curves_input),
+        )
+    });
 
-    rgb_values
-        // apply gray curve to the RGB curves, reducing 4 curves to 3 colour channel curves
-        .map(|color_curve| {
-            color_curve
-                .iter()
-                // values are scaled down to 0-255 as there are 256 values in each curve and used as gray input values
-                .map(|&color_value| gray[scale_u16_to_u8_range(color_value) as usize])
-                .collect::<Vec<u16>>()
+    match &args.csv {
+        Some(csv) => {
+            let contents = export::curves_to_csv(&curves, !args.no_header);
+            if to_stdout {
+                print!("{contents}");
+            } else {
+                info!("saving curves to {:?}...", csv);
+                fs::write(csv, contents).unwrap_or_else(|err| {
+                    fail(
+                        ErrorCategory::Io,
+                        format!("Error while saving CSV to {csv:?}: {err}"),
+                    )
+                });
+            }
+        }
+        None => {
+            for curve in curves {
+                let values: Vec<String> = curve.iter().map(|value| value.to_string()).collect();
+                println!("{}", values.join(" "));
+            }
+        }
+    }
+}
+
+/// Writes the curves as a .cube LUT for --cube instead of building a profile
+fn export_cube(cube: &Path, args: &Args) {
+    let curves_input = args.curves_input.as_ref().unwrap();
+    let input = read_input(curves_input);
+    let curves = prepare_curves(&input, curves_input, args).unwrap_or_else(|err| {
+        fail(
+            err.category(),
+            format!("Could not convert {:?}: {err}", curves_input),
+        )
+    });
+
+    let title = match &args.description {
+        Some(description) => description.clone(),
+        None => curves_input
+            .file_stem()
+            .map_or("Curves".into(), |stem| stem.to_string_lossy().into_owned()),
+    };
+    let contents = export::curves_to_cube(&curves, args.cube_size as usize, &title);
+    if cube.as_os_str() == "-" {
+        print!("{contents}");
+    } else {
+        info!("saving LUT to {:?}...", cube);
+        fs::write(cube, contents).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Error while saving LUT to {cube:?}: {err}"),
+            )
+        });
+    }
+}
+
+/// Writes a PNG of a gradient with the curves applied for --palette-preview instead of building
+/// a profile
+#[cfg(feature = "palette-preview")]
+fn export_palette_preview(png: &Path, args: &Args) {
+    let curves_input = args.curves_input.as_ref().unwrap();
+    let input = read_input(curves_input);
+    let curves = prepare_curves(&input, curves_input, args).unwrap_or_else(|err| {
+        fail(
+            err.category(),
+            format!("Could not convert {:?}: {err}", curves_input),
+        )
+    });
+
+    let contents = rs_gimp_to_icc::preview::render_gradient_png(&curves).unwrap_or_else(|err| {
+        fail(
+            ErrorCategory::Io,
+            format!("Error while encoding PNG: {err}"),
+        )
+    });
+    info!("saving gradient preview to {:?}...", png);
+    fs::write(png, contents).unwrap_or_else(|err| {
+        fail(
+            ErrorCategory::Io,
+            format!("Error while saving PNG to {png:?}: {err}"),
+        )
+    });
+}
+
+/// Applies the curves of a curve file to an image for the apply subcommand
+#[cfg(feature = "apply-image")]
+fn apply_to_image(curves_input: &Path, image: &Path, output: &Path, args: &Args) {
+    let input = read_input(curves_input);
+    let curves = prepare_curves(&input, curves_input, args).unwrap_or_else(|err| {
+        fail(
+            err.category(),
+            format!("Could not convert {:?}: {err}", curves_input),
+        )
+    });
+
+    info!("reading image {:?}...", image);
+    let pixels = image::open(image).unwrap_or_else(|err| {
+        let category = match err {
+            image::ImageError::IoError(_) => ErrorCategory::Io,
+            _ => ErrorCategory::Parse,
+        };
+        fail(category, format!("Could not read image {image:?}: {err}"))
+    });
+    let applied = rs_gimp_to_icc::apply::apply_curves(&pixels, &curves);
+    info!("saving image to {:?}...", output);
+    applied
+        .save_with_format(output, image::ImageFormat::Png)
+        .unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Error while saving image to {output:?}: {err}"),
+            )
+        });
+}
+
+/// Prints a summary of the curves for --preview instead of building a profile
+fn preview_curves(args: &Args) {
+    let curves_input = args.curves_input.as_ref().unwrap();
+    let input = read_input(curves_input);
+    let curves = prepare_curves(&input, curves_input, args).unwrap_or_else(|err| {
+        fail(
+            err.category(),
+            format!("Could not convert {:?}: {err}", curves_input),
+        )
+    });
+    let names = curve_names(&curves);
+
+    let mut gammas = Vec::new();
+    for (name, curve) in names.iter().zip(&curves) {
+        let last = curve.len() - 1;
+        let levels: Vec<String> = (0..=4)
+            .map(|i| format!("{:.3}", curve[last * i / 4] as f64 / u16::MAX as f64))
+            .collect();
+        let gamma = analysis::estimate_gamma(curve);
+        gammas.push(gamma);
+        println!(
+            "{name}: at 0%, 25%, 50%, 75%, 100%: {}, gamma ~{gamma:.2}",
+            levels.join(" ")
+        );
+    }
+    let average = gammas.iter().sum::<f32>() / gammas.len() as f32;
+    println!("average gamma ~{average:.2}");
+}
+
+/// Prints how much the curves of the input differ from those of another curve file for
+/// --compare instead of building a profile
+fn compare_curves(other: &Path, args: &Args) {
+    let curves_input = args.curves_input.as_ref().unwrap();
+    let prepare = |path: &Path| {
+        let input = read_input(path);
+        prepare_curves(&input, path, args).unwrap_or_else(|err| {
+            fail(err.category(), format!("Could not convert {path:?}: {err}"))
+        })
+    };
+    let curves = prepare(curves_input);
+    let other_curves = if other.as_os_str() == "srgb" {
+        let reference = transform::srgb_reference_curve(curves[0].len());
+        vec![reference; curves.len()]
+    } else {
+        prepare(other)
+    };
+    let names = curve_names(&curves);
+
+    let scale = u16::MAX as f64;
+    for ((name, curve), other_curve) in names.iter().zip(&curves).zip(&other_curves) {
+        let difference = analysis::compare_curves(curve, other_curve);
+        println!(
+            "{name}: max difference {:.4} at sample {}, mean difference {:.4}",
+            difference.max as f64 / scale,
+            difference.max_index,
+            difference.mean / scale
+        );
+    }
+}
+
+/// Prints the samples outside of the 0-1 range for --check, failing if any were found
+fn check_curves(args: &Args) {
+    let curves_input = args.curves_input.as_ref().unwrap();
+    let input = read_input(curves_input);
+    if args
+        .format
+        .unwrap_or_else(|| InputFormat::detect(curves_input, &input))
+        != InputFormat::Gimp
+    {
+        fail(ErrorCategory::Parse, "Only GIMP curve files can be checked");
+    }
+    let curves = parse_raw_samples(&String::from_utf8_lossy(&input), args.curve_index)
+        .unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Parse,
+                format!("Could not parse {:?}: {err}", curves_input),
+            )
+        });
+
+    let mut found = 0;
+    for (name, curve) in SAMPLE_CHANNELS.iter().zip(&curves) {
+        for (i, value) in validate::find_out_of_range(curve) {
+            println!("{name} sample {i}: {value}");
+            found += 1;
+        }
+    }
+    if found > 0 {
+        fail(
+            ErrorCategory::Validation,
+            format!("{found} samples are outside of the 0-1 range and will be clamped"),
+        );
+    }
+    info!("all samples are within the 0-1 range");
+}
+
+/// Writes the curves of the input file as JSON for --json instead of building a profile
+fn export_json(json: &Path, args: &Args) {
+    let curves_input = args.curves_input.as_ref().unwrap();
+    let input = read_input(curves_input);
+    let channels = detect_and_parse_channels(curves_input, &input, args.format, args.curve_index)
+        .unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Parse,
+                format!("Could not parse {:?}: {err}", curves_input),
+            )
+        });
+
+    let contents = export::channels_to_json(&channels);
+    if json.as_os_str() == "-" {
+        println!("{contents}");
+    } else {
+        info!("saving curves to {:?}...", json);
+        fs::write(json, contents).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Error while saving JSON to {json:?}: {err}"),
+            )
+        });
+    }
+}
+
+/// Prints the VCGT tag of a profile for the verify subcommand, failing if it has none
+fn verify_profile(path: &Path) {
+    let icc = Profile::new_file(path).unwrap_or_else(|err| {
+        fail(
+            ErrorCategory::Io,
+            format!("Could not read profile {path:?}: {err}"),
+        )
+    });
+    let Some(curves) = read_vcgt_curves(&icc) else {
+        fail(ErrorCategory::Other, format!("{path:?} has no VCGT tag"));
+    };
+
+    println!("{path:?} has a VCGT tag with {} channels", curves.len());
+    if let Some(source) = private_tag::read_private_tag(&icc, private_tag::SOURCE_TAG) {
+        let source = String::from_utf8_lossy(&source);
+        println!("made from {}", source.replace('\n', ", "));
+    }
+    if let Some(&[bits]) =
+        private_tag::read_private_tag(&icc, private_tag::TARGET_BITS_TAG).as_deref()
+    {
+        println!("meant for {bits}-bit panels");
+    }
+    for (name, curve) in CHANNEL_NAMES.iter().zip(&curves) {
+        let last = curve.len() - 1;
+        let samples: Vec<String> = (0..=4).map(|i| curve[last * i / 4].to_string()).collect();
+        println!(
+            "{name}: {} entries, at 0%, 25%, 50%, 75%, 100%: {}",
+            curve.len(),
+            samples.join(" ")
+        );
+    }
+}
+
+/// Builds a profile from a generated GIMP curve file with power function curves and checks that
+/// reading it back gives the expected curves, for the selftest command. Each step is printed
+/// with its result
+fn self_test() -> bool {
+    // value, red, green, blue and alpha curves
+    let gammas = [0.8, 1.0, 1.25, 1.0, 1.0];
+    let input: String = gammas
+        .iter()
+        .map(|gamma| {
+            let samples: Vec<String> = (0..256)
+                .map(|i| format!("{:.6}", (i as f64 / 255.0).powf(*gamma)))
+                .collect();
+            format!("(samples 256 {}))\n", samples.join(" "))
+        })
+        .collect();
+
+    let check = |step: &str, passed: bool| {
+        println!("{step}: {}", if passed { "PASS" } else { "FAIL" });
+        passed
+    };
+
+    let Ok(curves) = rs_gimp_to_icc::parse_curves(&input) else {
+        return check("parse sample curve", false);
+    };
+    // each channel has the value curve applied after it
+    let composed_correctly = curves.iter().zip(&gammas[1..]).all(|(curve, gamma)| {
+        curve.iter().enumerate().all(|(i, &value)| {
+            let expected = (i as f64 / 255.0).powf(gamma * gammas[0]) * u16::MAX as f64;
+            (value as f64 - expected).abs() <= 64.0
+        })
+    });
+    if !check(
+        "parse sample curve",
+        curves.len() == 3 && composed_correctly,
+    ) {
+        return false;
+    }
+
+    let Ok(icc) = rs_gimp_to_icc::build_profile(&curves, "Self test") else {
+        return check("build profile", false);
+    };
+    check("build profile", true);
+    let Ok(bytes) = profile_to_bytes(&icc) else {
+        return check("serialize profile", false);
+    };
+    check("serialize profile", true);
+
+    let read_back = Profile::new_icc(&bytes)
+        .ok()
+        .and_then(|icc| read_vcgt_curves(&icc));
+    let round_trips = read_back.is_some_and(|read_back| {
+        read_back.len() == 3
+            && read_back.iter().zip(&curves).all(|(curve, expected)| {
+                curve.len() == expected.len()
+                    && curve.iter().zip(expected).all(|(a, b)| a.abs_diff(*b) <= 1)
+            })
+    });
+    check("read back VCGT curves", round_trips)
+}
+
+/// Sets up logging to stderr, at the level given by RUST_LOG or otherwise by --quiet and
+/// --verbose. Messages are printed without timestamps like plain output
+fn init_logger(args: &Args) {
+    let level = if args.quiet {
+        "warn"
+    } else if args.verbose {
+        "debug"
+    } else {
+        "info"
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level))
+        .format(|buf, record| match record.level() {
+            Level::Error => writeln!(buf, "Error: {}", record.args()),
+            Level::Warn => writeln!(buf, "Warning: {}", record.args()),
+            _ => writeln!(buf, "{}", record.args()),
         })
-        .collect::<Vec<Vec<u16>>>()
+        .init();
 }
 
 fn main() {
-    let args = Args::parse();
-    let mut icc = Profile::new_srgb();
+    let args = apply_config(Args::parse());
+    init_logger(&args);
+
+    if args.mode == CurveMode::Both {
+        warn!("the curves are written as both VCGT and TRC, colour managed applications on a display with the VCGT loaded apply them twice");
+    }
+
+    match &args.command {
+        Some(Command::Verify { icc }) => {
+            verify_profile(icc);
+            return;
+        }
+        Some(Command::Selftest) => {
+            let passed = self_test();
+            println!("{}", if passed { "PASS" } else { "FAIL" });
+            if !passed {
+                process::exit(ErrorCategory::Other.exit_code());
+            }
+            return;
+        }
+        #[cfg(feature = "apply-image")]
+        Some(Command::Apply {
+            curves,
+            image,
+            output,
+        }) => {
+            apply_to_image(curves, image, output, &args);
+            return;
+        }
+        None => {}
+    }
 
-    icc.remove_tag(lcms2::TagSignature::ProfileDescriptionTag);
+    if args.print_loader_hint {
+        print!("{LOADER_HINT}");
+        return;
+    }
 
-    // description that is shown in Windows colour management
-    let mut desc = MLU::new(1);
-    desc.set_text(&args.description, Locale::none());
-    icc.write_tag(lcms2::TagSignature::ProfileDescriptionTag, Tag::MLU(&desc));
+    if let Some(dir) = &args.batch {
+        if !args.dry_run {
+            if let Err(message) = check_writable_dir(dir) {
+                fail(ErrorCategory::Io, message);
+            }
+        }
+        let failures = run_batch(dir, &args);
+        process::exit(batch_exit_code(&failures));
+    }
 
-    // curves are exported from GIMP curve tool
-    println!("reading curve samples from {:?}...", &args.curves_input);
-    let text = fs::read_to_string(&args.curves_input)
-        .unwrap_or_else(|err| panic!("Could not read file {:?}: {}", args.curves_input, err));
+    if args.merge_files.len() == 1 {
+        Args::command()
+            .error(
+                ErrorKind::TooFewValues,
+                "--merge-files needs at least two curve files separated by commas",
+            )
+            .exit();
+    }
+
+    if args.dump || args.csv.is_some() {
+        export_curves(&args);
+        return;
+    }
+
+    if args.preview {
+        preview_curves(&args);
+        return;
+    }
+
+    if let Some(other) = &args.compare {
+        compare_curves(other, &args);
+        return;
+    }
+
+    if let Some(cube) = &args.cube {
+        export_cube(cube, &args);
+        return;
+    }
+
+    #[cfg(feature = "palette-preview")]
+    if let Some(png) = &args.palette_preview {
+        export_palette_preview(png, &args);
+        return;
+    }
 
-    let rgb_curves = parse_curves(text);
+    if args.check {
+        check_curves(&args);
+        return;
+    }
+
+    if let Some(json) = &args.json {
+        export_json(json, &args);
+        return;
+    }
+
+    let icc_output = args.output_path();
+    let to_stdout = icc_output.as_os_str() == "-";
+    if !to_stdout && !args.dry_run && icc_output.exists() && !args.force {
+        fail(
+            ErrorCategory::Io,
+            format!("Output file {icc_output:?} already exists, pass --force to overwrite it"),
+        );
+    }
+    if !to_stdout && !args.dry_run {
+        if let Err(message) = check_writable_dir(output_dir(&icc_output)) {
+            fail(ErrorCategory::Io, message);
+        }
+    }
 
-    let r_tc = ToneCurve::new_tabulated(&rgb_curves[0]);
-    let g_tc = ToneCurve::new_tabulated(&rgb_curves[1]);
-    let b_tc = ToneCurve::new_tabulated(&rgb_curves[2]);
+    let mut icc = match args.gamma {
+        None if args.identity => {
+            info!("building identity curves...");
+            let description = args.description.as_deref().unwrap_or(IDENTITY_DESCRIPTION);
+            let curve = transform::identity_curve(args.generated_samples());
+            built(build_profile_with_options(
+                &vec![curve; 3],
+                &args.profile_options(description),
+            ))
+        }
+        None if args.reset_srgb => {
+            info!("building sRGB curves...");
+            let description = args
+                .description
+                .as_deref()
+                .unwrap_or(SRGB_RESET_DESCRIPTION);
+            let curve = transform::srgb_reference_curve(args.generated_samples());
+            built(build_profile_with_options(
+                &vec![curve; 3],
+                &args.profile_options(description),
+            ))
+        }
+        Some(gamma) => {
+            if let Err(err) = check_gamma_bounds("--gamma", gamma as f32, &args) {
+                fail(err.category(), err.to_string());
+            }
+            info!("building curves with gamma {gamma}...");
+            let description = args.description.as_deref().unwrap_or(DEFAULT_DESCRIPTION);
+            built(build_gamma_profile(
+                gamma,
+                &args.profile_options(description),
+            ))
+        }
+        None if !args.merge_files.is_empty() => convert_merged(&args.merge_files, &args),
+        None if args.curve_base64.is_some() => {
+            convert_base64(args.curve_base64.as_deref().unwrap(), &args)
+        }
+        None => convert_input(args.curves_input.as_ref().unwrap(), &args),
+    };
 
-    let tc_refs: [&lcms2::ToneCurveRef; 3] = [&r_tc, &g_tc, &b_tc];
-    let vcgt_tag = Tag::VcgtCurves(tc_refs);
-    icc.write_tag(lcms2::TagSignature::VcgtTag, vcgt_tag);
+    if args.print_sha256 {
+        print_profile_hash(&icc, &icc_output).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Could not serialize profile: {err}"),
+            )
+        });
+    }
+
+    if args.dry_run {
+        if let Err(err) = check_serializes(&icc) {
+            fail(
+                ErrorCategory::Io,
+                format!("Could not serialize profile: {err}"),
+            );
+        }
+        info!("dry run passed, the profile was not written");
+    } else if to_stdout {
+        info!("saving profile to stdout...");
+        let bytes = profile_to_bytes(&icc).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Error while serializing profile: {err}"),
+            )
+        });
+        io::stdout().write_all(&bytes).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Error while writing profile to stdout: {err}"),
+            )
+        });
+    } else {
+        info!("saving profile to {:?}...", icc_output);
+        icc.save_profile_to_file(&icc_output).unwrap_or_else(|err| {
+            fail(
+                ErrorCategory::Io,
+                format!("Error while saving profile to {icc_output:?}: {err}"),
+            )
+        });
+    }
+    // files with only a value curve become grayscale profiles without a gamma table
+    if read_vcgt_curves(&icc).is_some() {
+        log_loader_note(&args);
+    }
+}
 
-    println!("saving profile to {:?}...", args.icc_output);
-    icc.save_profile_to_file(args.icc_output.as_path())
-        .unwrap_or_else(|err| panic!("Error while saving profile to: {err}",));
+/// Logs that Windows needs something to load the gamma table, after saving profiles that have
+/// one, as the profile alone seemingly does nothing otherwise
+fn log_loader_note(args: &Args) {
+    if args.no_loader_note || args.dry_run || args.grayscale || args.mode == CurveMode::Trc {
+        return;
+    }
+    info!("note: Windows only applies the gamma table when it is loaded at login, run with --print-loader-hint for how to set that up");
 }
 
 #[cfg(test)]
@@ -112,84 +1938,139 @@ mod tests {
     use super::*;
 
     #[test]
-    /// Tests the entirety of parse_curves() with a known example
-    fn parsing_example_input_works() {
-        let input = fs::read_to_string("test/gimp_test_curve.txt").unwrap();
-        let parsed_result = parse_curves(input);
-
-        // big array
-        let expected = vec![
-            vec![
-                0, 0, 0, 0, 232, 463, 696, 928, 1161, 1395, 1630, 1867, 2104, 2343, 2584, 2826,
-                3070, 3317, 3565, 3816, 4070, 4327, 4586, 4848, 5120, 5387, 5655, 5923, 6192, 6461,
-                6730, 7000, 7270, 7542, 7813, 8086, 8359, 8634, 8909, 9185, 9462, 9741, 10020,
-                10301, 10583, 10866, 11151, 11437, 11725, 12014, 12305, 12597, 12891, 13187, 13485,
-                13785, 14087, 14390, 14696, 14848, 15156, 15466, 15776, 16088, 16401, 16714, 17029,
-                17345, 17661, 17978, 18296, 18615, 18935, 19255, 19576, 19898, 20221, 20544, 20868,
-                21192, 21517, 21843, 22168, 22495, 22822, 23149, 23477, 23805, 24133, 24462, 24791,
-                25120, 25449, 25779, 26109, 26439, 26769, 27099, 27429, 27759, 28089, 28420, 28750,
-                29080, 29410, 29739, 30069, 30398, 30728, 31056, 31385, 31713, 32041, 32369, 32696,
-                33023, 33350, 33675, 34001, 34326, 34650, 34974, 35297, 35619, 35941, 36262, 36583,
-                36902, 37221, 37539, 37856, 38172, 38488, 38802, 39116, 39428, 39740, 40051, 40360,
-                40668, 40976, 41282, 41587, 41890, 42193, 42494, 42794, 43093, 43390, 43686, 43981,
-                44274, 44565, 44856, 45144, 45432, 45717, 46001, 46283, 46564, 46847, 47123, 47394,
-                47660, 47921, 48178, 48430, 48678, 48922, 49162, 49398, 49630, 49858, 50083, 50305,
-                50524, 50739, 50952, 51161, 51368, 51573, 51775, 51974, 52172, 52367, 52561, 52752,
-                52943, 53131, 53318, 53504, 53689, 53873, 54056, 54238, 54420, 54601, 54782, 54963,
-                55144, 55324, 55505, 55687, 55868, 56051, 56234, 56418, 56603, 56789, 56976, 57165,
-                57355, 57547, 57855, 58050, 58245, 58438, 58631, 58823, 59014, 59205, 59394, 59583,
-                59772, 59959, 60146, 60333, 60518, 60703, 60888, 61072, 61255, 61438, 61621, 61803,
-                61984, 62166, 62346, 62527, 62707, 62886, 63066, 63245, 63424, 63602, 63780, 63959,
-                64136, 64314, 64492, 64669, 64847, 65024, 65201, 65378, 65535,
-            ],
-            vec![
-                0, 0, 0, 0, 232, 463, 696, 928, 1161, 1395, 1630, 1867, 2104, 2343, 2584, 2826,
-                3070, 3317, 3565, 3816, 4070, 4327, 4586, 4848, 5120, 5387, 5655, 5923, 6192, 6461,
-                6730, 7000, 7270, 7542, 7813, 8086, 8359, 8634, 8909, 9185, 9462, 9741, 10020,
-                10301, 10583, 10866, 11151, 11437, 11725, 12014, 12305, 12597, 12891, 13187, 13485,
-                13785, 14087, 14390, 14696, 14848, 15156, 15466, 15776, 16088, 16401, 16714, 17029,
-                17345, 17661, 17978, 18296, 18615, 18935, 19255, 19576, 19898, 20221, 20544, 20868,
-                21192, 21517, 21843, 22168, 22495, 22822, 23149, 23477, 23805, 24133, 24462, 24791,
-                25120, 25449, 25779, 26109, 26439, 26769, 27099, 27429, 27759, 28089, 28420, 28750,
-                29080, 29410, 29739, 30069, 30398, 30728, 31056, 31385, 31713, 32041, 32369, 32696,
-                33023, 33350, 33675, 34001, 34326, 34650, 34974, 35297, 35619, 35941, 36262, 36583,
-                36902, 37221, 37539, 37856, 38172, 38488, 38802, 39116, 39428, 39740, 40051, 40360,
-                40668, 40976, 41282, 41587, 41890, 42193, 42494, 42794, 43093, 43390, 43686, 43981,
-                44274, 44565, 44856, 45144, 45432, 45717, 46001, 46283, 46564, 46847, 47123, 47394,
-                47660, 47921, 48178, 48430, 48678, 48922, 49162, 49398, 49630, 49858, 50083, 50305,
-                50524, 50739, 50952, 51161, 51368, 51573, 51775, 51974, 52172, 52367, 52561, 52752,
-                52943, 53131, 53318, 53504, 53689, 53873, 54056, 54238, 54420, 54601, 54782, 54963,
-                55144, 55324, 55505, 55687, 55868, 56051, 56234, 56418, 56603, 56789, 56976, 57165,
-                57355, 57547, 57855, 58050, 58245, 58438, 58631, 58823, 59014, 59205, 59394, 59583,
-                59772, 59959, 60146, 60333, 60518, 60703, 60888, 61072, 61255, 61438, 61621, 61803,
-                61984, 62166, 62346, 62527, 62707, 62886, 63066, 63245, 63424, 63602, 63780, 63959,
-                64136, 64314, 64492, 64669, 64847, 65024, 65201, 65378, 65535,
-            ],
-            vec![
-                0, 0, 0, 0, 232, 463, 696, 928, 1161, 1395, 1630, 1867, 2104, 2343, 2584, 2826,
-                3070, 3317, 3565, 3816, 4070, 4327, 4586, 4848, 5120, 5387, 5655, 5923, 6192, 6461,
-                6730, 7000, 7270, 7542, 7813, 8086, 8359, 8634, 8909, 9185, 9462, 9741, 10020,
-                10301, 10583, 10866, 11151, 11437, 11725, 12014, 12305, 12597, 12891, 13187, 13485,
-                13785, 14087, 14390, 14696, 14848, 15156, 15466, 15776, 16088, 16401, 16714, 17029,
-                17345, 17661, 17978, 18296, 18615, 18935, 19255, 19576, 19898, 20221, 20544, 20868,
-                21192, 21517, 21843, 22168, 22495, 22822, 23149, 23477, 23805, 24133, 24462, 24791,
-                25120, 25449, 25779, 26109, 26439, 26769, 27099, 27429, 27759, 28089, 28420, 28750,
-                29080, 29410, 29739, 30069, 30398, 30728, 31056, 31385, 31713, 32041, 32369, 32696,
-                33023, 33350, 33675, 34001, 34326, 34650, 34974, 35297, 35619, 35941, 36262, 36583,
-                36902, 37221, 37539, 37856, 38172, 38488, 38802, 39116, 39428, 39740, 40051, 40360,
-                40668, 40976, 41282, 41587, 41890, 42193, 42494, 42794, 43093, 43390, 43686, 43981,
-                44274, 44565, 44856, 45144, 45432, 45717, 46001, 46283, 46564, 46847, 47123, 47394,
-                47660, 47921, 48178, 48430, 48678, 48922, 49162, 49398, 49630, 49858, 50083, 50305,
-                50524, 50739, 50952, 51161, 51368, 51573, 51775, 51974, 52172, 52367, 52561, 52752,
-                52943, 53131, 53318, 53504, 53689, 53873, 54056, 54238, 54420, 54601, 54782, 54963,
-                55144, 55324, 55505, 55687, 55868, 56051, 56234, 56418, 56603, 56789, 56976, 57165,
-                57355, 57547, 57855, 58050, 58245, 58438, 58631, 58823, 59014, 59205, 59394, 59583,
-                59772, 59959, 60146, 60333, 60518, 60703, 60888, 61072, 61255, 61438, 61621, 61803,
-                61984, 62166, 62346, 62527, 62707, 62886, 63066, 63245, 63424, 63602, 63780, 63959,
-                64136, 64314, 64492, 64669, 64847, 65024, 65201, 65378, 65535,
-            ],
+    fn missing_output_directory_is_an_error() {
+        let output = Path::new("no_such_directory/out.icc");
+        let err = check_writable_dir(output_dir(output)).unwrap_err();
+        assert!(err.contains("does not exist"), "{err}");
+
+        assert_eq!(output_dir(Path::new("out.icc")), Path::new("."));
+        assert!(check_writable_dir(&env::temp_dir()).is_ok());
+    }
+
+    #[test]
+    fn command_line_takes_precedence_over_config() {
+        let config = parse_config(
+            "description = \"Team profile\"\nmode = \"trc\"\nsamples = 1024\n",
+            Path::new("team.toml"),
+        )
+        .unwrap();
+        let argv = [
+            "rs-gimp-to-icc",
+            "curves.txt",
+            "--config",
+            "team.toml",
+            "--samples",
+            "512",
+        ];
+        let args = merge_config(argv.map(OsString::from).to_vec(), &config).unwrap();
+
+        assert_eq!(args.description.as_deref(), Some("Team profile"));
+        assert_eq!(args.mode, CurveMode::Trc);
+        assert_eq!(args.samples, Some(512));
+        assert!(parse_config("sample = 1", Path::new("team.toml")).is_err());
+        assert!(parse_config(r#"{"samples": 512}"#, Path::new("team.json")).is_ok());
+    }
+
+    #[test]
+    fn config_defaults_give_way_to_conflicting_flags() {
+        let config = parse_config(
+            "bit-depth = \"8\"\nmode = \"trc\"\n",
+            Path::new("team.toml"),
+        )
+        .unwrap();
+        let merge =
+            |argv: &[&str]| merge_config(argv.iter().map(OsString::from).collect(), &config);
+
+        let args = merge(&["rs-gimp-to-icc", "--gamma", "2.2", "-o", "out.icc"]).unwrap();
+        assert_eq!(args.bit_depth, BitDepth::Sixteen);
+        assert_eq!(args.mode, CurveMode::Trc);
+
+        let args = merge(&["rs-gimp-to-icc", "curves.txt", "--grayscale"]).unwrap();
+        assert_eq!(args.mode, CurveMode::Vcgt);
+        assert_eq!(args.bit_depth, BitDepth::Eight);
+
+        let config = parse_config("mode = \"sideways\"", Path::new("team.toml")).unwrap();
+        assert!(merge_config(vec!["rs-gimp-to-icc".into(), "curves.txt".into()], &config).is_err());
+    }
+
+    #[test]
+    fn curve_base64_is_decoded() {
+        let input = fs::read("test/gimp_test_curve.txt").unwrap();
+        let encoded = STANDARD.encode(&input);
+        let (start, end) = encoded.split_at(76);
+        assert_eq!(decode_curve_base64(&format!("{start}\n{end}\n")), Ok(input));
+
+        let err = decode_curve_base64("not base64!").unwrap_err();
+        assert!(err.starts_with("Invalid --curve-base64 data"), "{err}");
+    }
+
+    #[test]
+    fn gamma_outside_of_bounds_is_an_error() {
+        let args = Args::parse_from(["rs-gimp-to-icc", "curves.txt", "--max-gamma", "4"]);
+        assert!(check_gamma_bounds("channel R", 2.2, &args).is_ok());
+
+        let err = check_gamma_bounds("channel R", 5.0, &args).unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Validation);
+        assert!(check_gamma_bounds("channel R", 0.05, &args).is_err());
+    }
+
+    #[test]
+    fn error_categories_have_distinct_exit_codes() {
+        let categories = [
+            ErrorCategory::Other,
+            ErrorCategory::Parse,
+            ErrorCategory::Io,
+            ErrorCategory::Validation,
         ];
+        assert_eq!(categories.map(ErrorCategory::exit_code), [1, 2, 3, 4]);
+
+        let parse = ConvertError::Parse(CurveParseError::WrongCurveCount(2));
+        assert_eq!(parse.category().exit_code(), 2);
+        let exists = ConvertError::Io(io::Error::from(io::ErrorKind::AlreadyExists));
+        assert_eq!(exists.category().exit_code(), 3);
+    }
+
+    #[test]
+    fn batch_exits_with_the_shared_failure_code() {
+        assert_eq!(batch_exit_code(&[]), 0);
+        assert_eq!(
+            batch_exit_code(&[ErrorCategory::Parse, ErrorCategory::Parse]),
+            2
+        );
+        assert_eq!(
+            batch_exit_code(&[ErrorCategory::Io, ErrorCategory::Parse]),
+            1
+        );
+    }
+
+    #[test]
+    fn generated_curves_take_no_input_file() {
+        assert!(Args::try_parse_from(["rs-gimp-to-icc", "--gamma", "2.2", "curves.txt"]).is_err());
+        assert!(Args::try_parse_from(["rs-gimp-to-icc", "--identity", "a.txt", "b.icc"]).is_err());
+
+        let args = Args::parse_from(["rs-gimp-to-icc", "--gamma", "2.2", "-o", "gamma.icc"]);
+        assert_eq!(args.output_path(), Path::new("gamma.icc"));
+        let args = Args::parse_from(["rs-gimp-to-icc", "curves.txt", "curves.icc"]);
+        assert_eq!(args.output_path(), Path::new("curves.icc"));
+    }
+
+    #[test]
+    fn curves_keep_their_samples_without_samples_option() {
+        let line: Vec<String> = (0..1024).map(|i| (i as f64 / 1023.0).to_string()).collect();
+        let raw = vec![line.join(" "); 4].join("\n");
 
-        assert_eq!(parsed_result, expected);
+        let args = Args::parse_from(["rs-gimp-to-icc", "a.txt", "--format", "raw"]);
+        let curves = prepare_curves(raw.as_bytes(), Path::new("a.txt"), &args).unwrap();
+        assert_eq!(curves[0].len(), 1024);
+        let args = Args::parse_from([
+            "rs-gimp-to-icc",
+            "a.txt",
+            "--format",
+            "raw",
+            "--samples",
+            "256",
+        ]);
+        let curves = prepare_curves(raw.as_bytes(), Path::new("a.txt"), &args).unwrap();
+        assert_eq!(curves[0].len(), 256);
     }
 }
@@ -0,0 +1,63 @@
+//! Rendering the effect of the curves on a test gradient as a PNG image
+
+use crate::{lerp_lookup, scale_u16_to_u8_range};
+
+/// Width of the gradient, one column per 8-bit level
+const WIDTH: u32 = 256;
+/// Height of each of the two gradients
+const BAND_HEIGHT: u32 = 32;
+
+/// Renders a black to white gradient above the same gradient with the curves applied, as PNG
+/// file contents. With 3 curves each is applied to its own channel, and a single curve is applied
+/// to all of them
+pub fn render_gradient_png(curves: &[Vec<u16>]) -> Result<Vec<u8>, png::EncodingError> {
+    let channels: Vec<&Vec<u16>> = match curves.len() {
+        1 => vec![&curves[0]; 3],
+        _ => curves[..3].iter().collect(),
+    };
+
+    let before: Vec<u8> = (0..WIDTH).flat_map(|x| [x as u8; 3]).collect();
+    let after: Vec<u8> = (0..WIDTH)
+        .flat_map(|x| {
+            channels.iter().map(move |curve| {
+                scale_u16_to_u8_range(lerp_lookup(curve, x as f32 / (WIDTH - 1) as f32))
+            })
+        })
+        .collect();
+    let mut data = before.repeat(BAND_HEIGHT as usize);
+    data.extend(after.repeat(BAND_HEIGHT as usize));
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, WIDTH, BAND_HEIGHT * 2);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_is_mapped_through_curves() {
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        let inverted: Vec<u16> = ramp.iter().rev().copied().collect();
+        let png = render_gradient_png(&[ramp.clone(), inverted, ramp]).unwrap();
+
+        let decoder = png::Decoder::new(std::io::Cursor::new(png));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+
+        let row = |y: usize, x: usize| {
+            let at = (y * WIDTH as usize + x) * 3;
+            [pixels[at], pixels[at + 1], pixels[at + 2]]
+        };
+        assert_eq!(row(0, 10), [10, 10, 10]);
+        assert_eq!(row(BAND_HEIGHT as usize, 10), [10, 245, 10]);
+        assert_eq!(row(2 * BAND_HEIGHT as usize - 1, 255), [255, 0, 255]);
+    }
+}
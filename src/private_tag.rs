@@ -0,0 +1,141 @@
+//! Reading and writing private ICC tags, which lcms2 can carry through but not create
+
+use lcms2::Profile;
+
+/// Size of the ICC header, after which the tag count and tag table follow
+const HEADER_SIZE: usize = 128;
+/// Size of a single tag table entry: signature, offset and size
+const TAG_ENTRY_SIZE: usize = 12;
+/// Type signature of the ICC dataType that private tag contents are stored as
+const DATA_TYPE: &[u8; 4] = b"data";
+/// Location of the profile ID in the header, which is invalidated when tags change
+const PROFILE_ID: std::ops::Range<usize> = 84..100;
+
+/// Private tag holding the alpha curve as big endian 16-bit samples
+pub const ALPHA_CURVE_TAG: [u8; 4] = *b"gcAl";
+/// Private tag holding UTF-8 text that describes the curve file the profile was made from
+pub const SOURCE_TAG: [u8; 4] = *b"gcSr";
+/// Private tag holding the bit depth of the panel the curves are meant for as a single byte
+pub const TARGET_BITS_TAG: [u8; 4] = *b"gcBt";
+
+fn read_u32(bytes: &[u8], at: usize) -> usize {
+    u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+}
+
+fn write_u32(bytes: &mut [u8], at: usize, value: usize) {
+    bytes[at..at + 4].copy_from_slice(&(value as u32).to_be_bytes());
+}
+
+/// Finds the tag table entry with the given signature in serialized profile bytes
+fn find_tag_entry(icc: &[u8], signature: [u8; 4]) -> Option<usize> {
+    let count = read_u32(icc, HEADER_SIZE);
+    (0..count)
+        .map(|i| HEADER_SIZE + 4 + i * TAG_ENTRY_SIZE)
+        .find(|&at| icc[at..at + 4] == signature)
+}
+
+/// Adds a tag with the given signature and binary contents to serialized ICC profile bytes. The
+/// contents are stored as an ICC dataType. An existing tag with the signature, such as one kept
+/// from a base profile, is pointed at the new contents, as lcms2 rejects duplicate signatures.
+/// Its old contents are left unreferenced and dropped the next time lcms2 saves the profile
+fn add_tag_to_bytes(icc: &[u8], signature: [u8; 4], data: &[u8]) -> Vec<u8> {
+    let count = read_u32(icc, HEADER_SIZE);
+    let (mut out, entry) = match find_tag_entry(icc, signature) {
+        Some(entry) => (icc.to_vec(), entry),
+        None => {
+            let table_end = HEADER_SIZE + 4 + count * TAG_ENTRY_SIZE;
+            let mut out = Vec::with_capacity(icc.len() + TAG_ENTRY_SIZE + data.len() + 16);
+            out.extend_from_slice(&icc[..table_end]);
+            out.extend_from_slice(&[0; TAG_ENTRY_SIZE]);
+            out.extend_from_slice(&icc[table_end..]);
+
+            // existing tag data moved forward to make room for the new table entry
+            for i in 0..count {
+                let at = HEADER_SIZE + 4 + i * TAG_ENTRY_SIZE + 4;
+                let offset = read_u32(&out, at);
+                write_u32(&mut out, at, offset + TAG_ENTRY_SIZE);
+            }
+            write_u32(&mut out, HEADER_SIZE, count + 1);
+            (out, table_end)
+        }
+    };
+
+    // tag data is aligned to 4 bytes
+    out.resize(out.len().next_multiple_of(4), 0);
+    let offset = out.len();
+    out.extend_from_slice(DATA_TYPE);
+    out.extend_from_slice(&[0; 4]);
+    // binary data flag
+    out.extend_from_slice(&1u32.to_be_bytes());
+    out.extend_from_slice(data);
+    let size = out.len() - offset;
+    out.resize(out.len().next_multiple_of(4), 0);
+
+    out[entry..entry + 4].copy_from_slice(&signature);
+    write_u32(&mut out, entry + 4, offset);
+    write_u32(&mut out, entry + 8, size);
+    let len = out.len();
+    write_u32(&mut out, 0, len);
+    out[PROFILE_ID].fill(0);
+
+    out
+}
+
+/// Finds the binary contents of a tag stored as an ICC dataType in serialized profile bytes
+fn read_tag_from_bytes(icc: &[u8], signature: [u8; 4]) -> Option<&[u8]> {
+    let at = find_tag_entry(icc, signature)?;
+    let offset = read_u32(icc, at + 4);
+    let size = read_u32(icc, at + 8);
+    let tag = icc.get(offset..offset + size)?;
+    (tag.len() >= 12 && &tag[..4] == DATA_TYPE).then(|| &tag[12..])
+}
+
+/// Returns a copy of the profile with a private tag added. lcms2 keeps tags it doesn't know
+/// as they are, so the tag survives saving the returned profile. A tag the profile already has
+/// with the signature is replaced
+pub fn with_private_tag(
+    profile: &Profile,
+    signature: [u8; 4],
+    data: &[u8],
+) -> Result<Profile, lcms2::Error> {
+    let icc = profile.icc()?;
+    Profile::new_icc(&add_tag_to_bytes(&icc, signature, data))
+}
+
+/// Reads the contents of a private tag written with [with_private_tag]
+pub fn read_private_tag(profile: &Profile, signature: [u8; 4]) -> Option<Vec<u8>> {
+    let icc = profile.icc().ok()?;
+    read_tag_from_bytes(&icc, signature).map(|data| data.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_tag_round_trips() {
+        let profile = with_private_tag(&Profile::new_srgb(), *b"test", &[1, 2, 3]).unwrap();
+
+        assert_eq!(read_private_tag(&profile, *b"test"), Some(vec![1, 2, 3]));
+        assert_eq!(read_private_tag(&profile, *b"none"), None);
+        // existing tags still read back correctly after being moved
+        assert!(profile.has_tag(lcms2::TagSignature::RedTRCTag));
+        assert!(matches!(
+            profile.read_tag(lcms2::TagSignature::RedTRCTag),
+            lcms2::Tag::ToneCurve(_)
+        ));
+    }
+
+    #[test]
+    fn existing_private_tag_is_replaced() {
+        let profile = with_private_tag(&Profile::new_srgb(), *b"test", &[1, 2, 3]).unwrap();
+        let replaced = with_private_tag(&profile, *b"test", &[4, 5]).unwrap();
+
+        assert_eq!(read_private_tag(&replaced, *b"test"), Some(vec![4, 5]));
+        let icc = replaced.icc().unwrap();
+        assert_eq!(
+            read_u32(&icc, HEADER_SIZE),
+            read_u32(&profile.icc().unwrap(), HEADER_SIZE)
+        );
+    }
+}
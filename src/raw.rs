@@ -0,0 +1,99 @@
+//! Parsing of plain text curve files without GIMP's (samples ...) wrapping
+
+use crate::{
+    compose_channels, parse_u16_curve_vec, CurveChannels, CurveParseError, ParseOptions,
+    SAMPLE_CHANNELS,
+};
+
+/// Parses plain curve data into 3 colour channel curves, and possibly an alpha curve, like
+/// [crate::parse_curves_with_options]. See [parse_raw_channels] for the format
+pub fn parse_raw_with_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<Vec<Vec<u16>>, CurveParseError> {
+    let channels = parse_raw_channels(text)?;
+    let mut curves = vec![channels.gray, channels.red, channels.green, channels.blue];
+    curves.extend(channels.alpha);
+
+    let mut curves = compose_channels(curves, options);
+    if !options.include_alpha {
+        curves.truncate(3);
+    }
+    Ok(curves)
+}
+
+/// Parses plain curve data with the value, red, green, blue and optionally alpha curves on one
+/// line each as space separated 0-1 values, the same way GIMP saves its samples. Empty lines and
+/// lines starting with # are skipped. Every curve must have as many values as the value curve
+pub fn parse_raw_channels(text: &str) -> Result<CurveChannels, CurveParseError> {
+    let lines: Vec<(usize, &str)> = text
+        .lines()
+        .map(|line| line.trim())
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    if !(4..=5).contains(&lines.len()) {
+        return Err(CurveParseError::WrongCurveCount(lines.len()));
+    }
+
+    let curves = lines
+        .iter()
+        .zip(SAMPLE_CHANNELS)
+        .map(|(&(index, line), name)| {
+            parse_u16_curve_vec(line).map_err(|err| err.at(index + 1, name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let expected = curves[0].len();
+    if expected < 2 {
+        return Err(CurveParseError::BadSampleLength {
+            expected: 2,
+            found: expected,
+        });
+    }
+    if let Some(curve) = curves[1..].iter().find(|curve| curve.len() != expected) {
+        return Err(CurveParseError::BadSampleLength {
+            expected,
+            found: curve.len(),
+        });
+    }
+    log::info!(
+        "detected raw format, {expected} samples, {} channels",
+        curves.len()
+    );
+
+    Ok(CurveChannels::from_curves(curves, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_lines_are_parsed_as_channels() {
+        let input = "# gray, r, g, b\n0 0.5 1\n0 0.25 1\n\n0 0.5 1\n0 1 1\n";
+        let channels = parse_raw_channels(input).unwrap();
+
+        assert_eq!(channels.gray, vec![0, 32768, 65535]);
+        assert_eq!(channels.red, vec![0, 16384, 65535]);
+        assert_eq!(channels.alpha, None);
+
+        let curves = parse_raw_with_options(input, &ParseOptions::default()).unwrap();
+        assert_eq!(curves.len(), 3);
+        assert_eq!(curves[2], vec![0, 65535, 65535]);
+    }
+
+    #[test]
+    fn mismatched_raw_counts_fail() {
+        assert_eq!(
+            parse_raw_channels("0 1\n0 1\n0 1\n").unwrap_err(),
+            CurveParseError::WrongCurveCount(3)
+        );
+        assert_eq!(
+            parse_raw_channels("0 1\n0 1\n0 0.5 1\n0 1\n").unwrap_err(),
+            CurveParseError::BadSampleLength {
+                expected: 2,
+                found: 3
+            }
+        );
+    }
+}
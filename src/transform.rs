@@ -0,0 +1,448 @@
+//! Transformations applied to tabulated curves after parsing
+
+/// Converts a linear light value in the 0-1 range to the sRGB encoding
+fn srgb_encode(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts an sRGB encoded value in the 0-1 range to linear light
+fn srgb_decode(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Reads a curve at a fractional index by linearly interpolating between the neighbouring samples
+fn lookup(curve: &[u16], index: f64) -> f64 {
+    let low = index.floor() as usize;
+    let high = (low + 1).min(curve.len() - 1);
+    let fraction = index - low as f64;
+    curve[low] as f64 * (1.0 - fraction) + curve[high] as f64 * fraction
+}
+
+/// Builds a curve with the given amount of samples that maps every value to itself, spanning
+/// the full 0-65535 range
+pub fn identity_curve(samples: usize) -> Vec<u16> {
+    let last = samples.saturating_sub(1).max(1) as f64;
+    (0..samples)
+        .map(|i| (i as f64 / last * u16::MAX as f64).round() as u16)
+        .collect()
+}
+
+/// Builds the sRGB transfer function from encoded values to linear light, the tone curve of an
+/// sRGB profile, with the given amount of samples. Useful as a reference to compare curves with
+pub fn srgb_reference_curve(samples: usize) -> Vec<u16> {
+    let last = samples.saturating_sub(1).max(1) as f64;
+    (0..samples)
+        .map(|i| (srgb_decode(i as f64 / last) * u16::MAX as f64).round() as u16)
+        .collect()
+}
+
+/// Converts a curve that GIMP applies in linear light to an equivalent curve applied to sRGB
+/// encoded values. Each sRGB input is decoded to linear light, looked up from the curve and the
+/// output is encoded back to sRGB, so the endpoints 0 and 65535 map to themselves
+pub fn delinearize_curve(samples: &[u16]) -> Vec<u16> {
+    let last = (samples.len() - 1) as f64;
+    (0..samples.len())
+        .map(|i| {
+            let linear_input = srgb_decode(i as f64 / last);
+            let linear_output = lookup(samples, linear_input * last) / u16::MAX as f64;
+            (srgb_encode(linear_output) * u16::MAX as f64)
+                .round()
+                .clamp(0.0, u16::MAX as f64) as u16
+        })
+        .collect()
+}
+
+/// Resamples a curve to the given amount of samples by linearly interpolating between the
+/// original samples. The first and last samples are kept as they are, as they fall exactly on
+/// the original endpoints
+pub fn resample_curve(curve: &[u16], samples: usize) -> Vec<u16> {
+    let scale = (curve.len() - 1) as f64 / (samples - 1) as f64;
+    (0..samples)
+        .map(|i| lookup(curve, i as f64 * scale).round() as u16)
+        .collect()
+}
+
+/// Averages several curves sample by sample, e.g. from repeated measurement runs. Curves with
+/// fewer samples are first resampled to the amount of the longest one, so that the averaged
+/// samples are at the same inputs
+pub fn average_curves(curves: &[Vec<u16>]) -> Vec<u16> {
+    let samples = curves.iter().map(Vec::len).max().unwrap_or(0);
+    let resampled: Vec<Vec<u16>> = curves
+        .iter()
+        .map(|curve| resample_curve(curve, samples))
+        .collect();
+    (0..samples)
+        .map(|i| {
+            let sum: u64 = resampled.iter().map(|curve| curve[i] as u64).sum();
+            (sum as f64 / curves.len() as f64).round() as u16
+        })
+        .collect()
+}
+
+/// Applies the second curve after the first, so that each output is second(first(x)). The
+/// result has as many samples as the first curve. Each of its outputs is interpolated between
+/// the sample of the second curve at [crate::scale_index] and the one after it, so the curves
+/// can have different amounts of samples
+///
+/// # Panics
+///
+/// If the second curve is empty, like [crate::lerp_lookup]
+pub fn compose_curves(first: &[u16], second: &[u16]) -> Vec<u16> {
+    let mut result = first.to_vec();
+    compose_curves_in_place(&mut result, second);
+    result
+}
+
+/// [compose_curves] that writes the outputs over the first curve instead of allocating a new
+/// one. Only integer math is done for each sample
+pub fn compose_curves_in_place(first: &mut [u16], second: &[u16]) {
+    let max = u16::MAX as u64;
+    let last = second.len() - 1;
+    for value in first {
+        let low = crate::scale_index(*value, second.len());
+        let high = (low + 1).min(last);
+        // the part of the way from the low to the high sample, out of 65535
+        let fraction = *value as u64 * last as u64 % max;
+        let mixed = second[low] as u64 * (max - fraction) + second[high] as u64 * fraction;
+        *value = ((mixed + max / 2) / max) as u16;
+    }
+}
+
+/// Applies a curve to its own output the given amount of times, so that 2 gives
+/// curve(curve(x)). A count of 0 or 1 returns the curve as it is
+pub fn repeat_curve(curve: &[u16], times: usize) -> Vec<u16> {
+    let mut result = curve.to_vec();
+    for _ in 1..times {
+        result = compose_curves(&result, curve);
+    }
+    result
+}
+
+/// Remaps each sample of a curve through a power function, output = input ^ gamma, with the
+/// samples treated as 0-1 values. A gamma of 1.0 leaves the curve as it is
+pub fn apply_gamma(curve: &[u16], gamma: f32) -> Vec<u16> {
+    curve
+        .iter()
+        .map(|&value| {
+            ((value as f32 / u16::MAX as f32).powf(gamma) * u16::MAX as f32).round() as u16
+        })
+        .collect()
+}
+
+/// Inverts a monotonically non-decreasing curve, so that applying the result after the curve
+/// gives back the input. The result has as many samples as the curve. Where the curve is flat
+/// the midpoint of the flat region is used, and outputs the curve never reaches are held at the
+/// nearest end
+pub fn invert_curve(curve: &[u16]) -> Vec<u16> {
+    let last = (curve.len() - 1) as f64;
+    (0..curve.len())
+        .map(|j| {
+            let target = (j as f64 / last * u16::MAX as f64).round() as u16;
+            // samples in low..high are exactly at the target
+            let low = curve.partition_point(|&value| value < target);
+            let high = curve.partition_point(|&value| value <= target);
+
+            let index = if low == curve.len() {
+                last
+            } else if high > low {
+                (low + high - 1) as f64 / 2.0
+            } else if low == 0 {
+                0.0
+            } else {
+                let (a, b) = (curve[low - 1] as f64, curve[low] as f64);
+                (low - 1) as f64 + (target as f64 - a) / (b - a)
+            };
+            (index / last * u16::MAX as f64).round() as u16
+        })
+        .collect()
+}
+
+/// Smooths a curve with a centered moving average over `window` samples. Near the ends the
+/// window shrinks so that it stays centered, which keeps the endpoints pinned and leaves
+/// straight sections of the curve as they are
+pub fn smooth_curve(curve: &[u16], window: usize) -> Vec<u16> {
+    let radius = window / 2;
+    (0..curve.len())
+        .map(|i| {
+            let radius = radius.min(i).min(curve.len() - 1 - i);
+            let samples = &curve[i - radius..=i + radius];
+            let sum: u64 = samples.iter().map(|&value| value as u64).sum();
+            (sum as f64 / samples.len() as f64).round() as u16
+        })
+        .collect()
+}
+
+/// Linearly rescales a curve so that its lowest sample becomes 0 and its highest 65535,
+/// stretching the contrast of curves that don't span the full output range. A flat curve has
+/// no range to stretch and is returned as it is
+pub fn stretch_range(curve: &[u16]) -> Vec<u16> {
+    let (Some(&min), Some(&max)) = (curve.iter().min(), curve.iter().max()) else {
+        return curve.to_vec();
+    };
+    if min == max {
+        return curve.to_vec();
+    }
+    let scale = u16::MAX as f64 / (max - min) as f64;
+    curve
+        .iter()
+        .map(|&value| ((value - min) as f64 * scale).round() as u16)
+        .collect()
+}
+
+/// Rounds each sample of a curve to the nearest of the levels that a panel with the given bit
+/// depth can show, e.g. 1024 levels for 10 bits, so that the curve doesn't imply precision the
+/// panel doesn't have. The values stay scaled to 0-65535
+pub fn quantize_curve(curve: &[u16], bits: u8) -> Vec<u16> {
+    let levels = ((1u32 << bits) - 1) as f64;
+    let max = u16::MAX as f64;
+    curve
+        .iter()
+        .map(|&value| ((value as f64 / max * levels).round() / levels * max).round() as u16)
+        .collect()
+}
+
+/// Largest distance in 16-bit steps from 0 and 65535 at which [snap_endpoints] moves the first
+/// and last samples onto the full range
+pub const SNAP_TOLERANCE: u16 = 16;
+
+/// Moves the first sample to 0 and the last to 65535 when rounding has left them slightly off,
+/// as some displays reject gamma tables that don't span the full range. Endpoints further away
+/// than [SNAP_TOLERANCE] are an intentional part of the curve and are kept
+pub fn snap_endpoints(curve: &[u16]) -> Vec<u16> {
+    let mut result = curve.to_vec();
+    if let Some(first) = result.first_mut().filter(|first| **first <= SNAP_TOLERANCE) {
+        *first = 0;
+    }
+    if let Some(last) = result
+        .last_mut()
+        .filter(|last| **last >= u16::MAX - SNAP_TOLERANCE)
+    {
+        *last = u16::MAX;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delinearize_keeps_endpoints_and_identity() {
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        let result = delinearize_curve(&ramp);
+
+        assert_eq!(result[0], 0);
+        assert_eq!(result[255], 65535);
+        // an identity curve is the identity in any encoding
+        for (&a, &b) in result.iter().zip(&ramp) {
+            assert!(a.abs_diff(b) <= 1, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn delinearize_brightens_linear_midpoint() {
+        // a linear light curve that halves everything is darker than halving in sRGB
+        let half: Vec<u16> = (0..256).map(|x| x * 257 / 2).collect();
+        let result = delinearize_curve(&half);
+
+        assert_eq!(result[0], 0);
+        // 0.5 linear light is ~0.735 in sRGB
+        assert!((result[255] as f64 / 65535.0 - 0.7354).abs() < 0.001);
+    }
+
+    #[test]
+    fn resample_interpolates_and_keeps_endpoints() {
+        let curve = vec![0, 1000, 65535];
+
+        assert_eq!(resample_curve(&curve, 5), vec![0, 500, 1000, 33268, 65535]);
+        assert_eq!(resample_curve(&curve, 2), vec![0, 65535]);
+        assert_eq!(resample_curve(&curve, 3), curve);
+    }
+
+    #[test]
+    fn gamma_of_one_is_identity() {
+        let curve: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        assert_eq!(apply_gamma(&curve, 1.0), curve);
+    }
+
+    #[test]
+    fn gamma_bends_midtones_only() {
+        let curve = vec![0, 16384, 32768, 65535];
+        let darker = apply_gamma(&curve, 2.0);
+
+        assert_eq!(darker, vec![0, 4096, 16384, 65535]);
+    }
+
+    #[test]
+    fn inverting_twice_is_identity() {
+        let curve = apply_gamma(&(0..256).map(|x| x * 257).collect::<Vec<u16>>(), 0.8);
+        let inverse = invert_curve(&curve);
+
+        // the inverse of brightening darkens
+        assert!(inverse[128] < 128 * 257);
+        for (&a, &b) in invert_curve(&inverse).iter().zip(&curve) {
+            assert!(a.abs_diff(b) <= 300, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn inverting_flat_region_uses_midpoint() {
+        let curve = vec![0, 32768, 32768, 32768, 65535];
+        assert_eq!(invert_curve(&curve), vec![0, 8192, 32768, 57343, 65535]);
+    }
+
+    #[test]
+    fn smoothing_keeps_endpoints_and_flattens_noise() {
+        let noisy: Vec<u16> = (0..256u32)
+            .map(|x| (x * 257 + if x % 2 == 0 { 2000 } else { 0 }).min(65535) as u16)
+            .collect();
+        let result = smooth_curve(&noisy, 5);
+
+        assert_eq!(result[0], noisy[0]);
+        assert_eq!(result[255], noisy[255]);
+
+        let roughness = |curve: &[u16]| -> u32 {
+            curve
+                .windows(3)
+                .map(|w| (w[0] as i32 - 2 * w[1] as i32 + w[2] as i32).unsigned_abs())
+                .sum()
+        };
+        assert!(roughness(&result) * 4 < roughness(&noisy));
+    }
+
+    #[test]
+    fn smoothing_keeps_ramp() {
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        assert_eq!(smooth_curve(&ramp, 7), ramp);
+        assert_eq!(smooth_curve(&ramp, 1), ramp);
+    }
+
+    #[test]
+    fn endpoints_are_snapped_to_full_range() {
+        let curve = crate::parse_u16_curve_vec("0.0001 0.5 0.9998").unwrap();
+        assert_eq!(curve, vec![7, 32768, 65522]);
+        assert_eq!(snap_endpoints(&curve), vec![0, 32768, 65535]);
+
+        // intentionally raised blacks and lowered whites are kept
+        assert_eq!(snap_endpoints(&[1000, 60000]), vec![1000, 60000]);
+    }
+
+    #[test]
+    fn srgb_reference_curve_matches_known_values() {
+        let curve = srgb_reference_curve(256);
+        assert_eq!(curve[0], 0);
+        assert_eq!(curve[255], u16::MAX);
+        // 10 is in the linear segment, 10 / 255 / 12.92 of full scale
+        assert_eq!(curve[10], 199);
+        // 128 decodes to about 0.2158 and 188 to about 0.5
+        assert!((curve[128] as f64 / 65535.0 - 0.2158).abs() < 1e-4);
+        assert!((curve[188] as f64 / 65535.0 - 0.5).abs() < 0.005);
+        // encoding the samples again gives back the inputs
+        assert!(curve.iter().enumerate().all(|(i, &value)| {
+            (srgb_encode(value as f64 / 65535.0) * 255.0 - i as f64).abs() < 0.05
+        }));
+    }
+
+    #[test]
+    fn composing_with_identity_changes_nothing() {
+        let curve = apply_gamma(&identity_curve(256), 2.2);
+        assert_eq!(compose_curves(&curve, &identity_curve(256)), curve);
+        assert_eq!(compose_curves(&identity_curve(256), &curve), curve);
+        // a longer identity is read between its samples and still changes nothing
+        assert_eq!(compose_curves(&curve, &identity_curve(4096)), curve);
+
+        // x ^ 0.5 followed by x ^ 2 undo each other
+        let brighter = apply_gamma(&identity_curve(256), 0.5);
+        let darker = apply_gamma(&identity_curve(256), 2.0);
+        let composed = compose_curves(&brighter, &darker);
+        assert!(composed
+            .iter()
+            .zip(&identity_curve(256))
+            .all(|(a, b)| a.abs_diff(*b) <= 400));
+    }
+
+    #[test]
+    fn integer_composition_matches_float_lookup() {
+        let first = apply_gamma(&identity_curve(1024), 0.7);
+        let second = apply_gamma(&identity_curve(200), 1.8);
+        for (&value, composed) in first.iter().zip(compose_curves(&first, &second)) {
+            let expected = crate::lerp_lookup(&second, value as f32 / u16::MAX as f32);
+            assert!(composed.abs_diff(expected) <= 1, "{composed} != {expected}");
+        }
+    }
+
+    #[test]
+    fn repeated_curve_is_composed_with_itself() {
+        let curve = apply_gamma(&identity_curve(256), 2.0);
+        assert_eq!(repeat_curve(&curve, 1), curve);
+
+        // applying x ^ 2 twice gives x ^ 4
+        let twice = repeat_curve(&curve, 2);
+        let expected = apply_gamma(&identity_curve(256), 4.0);
+        assert!(twice
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| a.abs_diff(*b) <= 128));
+        assert_eq!(twice[0], 0);
+        assert_eq!(twice[255], u16::MAX);
+    }
+
+    #[test]
+    fn identity_curve_is_full_range_ramp() {
+        let curve = identity_curve(256);
+        assert_eq!(curve.len(), 256);
+        assert_eq!(curve[0], 0);
+        assert_eq!(curve[1], 257);
+        assert_eq!(curve[255], u16::MAX);
+        assert!(crate::validate::is_identity(&curve));
+    }
+
+    #[test]
+    fn stretching_fills_full_range() {
+        let compressed = [10000, 20000, 30000, 40000, 50000];
+        assert_eq!(
+            stretch_range(&compressed),
+            vec![0, 16384, 32768, 49151, 65535]
+        );
+
+        let descending: Vec<u16> = compressed.iter().rev().copied().collect();
+        assert_eq!(stretch_range(&descending)[0], u16::MAX);
+        assert_eq!(stretch_range(&[3000; 4]), vec![3000; 4]);
+    }
+
+    #[test]
+    fn quantizing_rounds_to_panel_levels() {
+        let ramp = identity_curve(4096);
+        let quantized = quantize_curve(&ramp, 10);
+
+        let mut levels = quantized.clone();
+        levels.dedup();
+        assert_eq!(levels.len(), 1024);
+        assert_eq!((quantized[0], quantized[4095]), (0, u16::MAX));
+        for (&a, &b) in quantized.iter().zip(&ramp) {
+            assert!(a.abs_diff(b) <= 33, "{a} != {b}");
+        }
+        assert_eq!(quantize_curve(&ramp, 16), ramp);
+    }
+
+    #[test]
+    fn averaging_resamples_to_longest_curve() {
+        let coarse = vec![0, 65535];
+        let fine = vec![0, 16384, 32768, 49152, 65535];
+        let dark = vec![0, 0, 0, 0, 0];
+
+        assert_eq!(average_curves(&[coarse.clone(), fine.clone()]), fine);
+        assert_eq!(
+            average_curves(&[coarse, dark]),
+            vec![0, 8192, 16384, 24576, 32768]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Sanity checks for parsed curves that point out curves which will probably look wrong
+
+/// Checks that a curve never decreases, which would show up as inverted tones on the display.
+/// Returns the first index where the curve is lower than the sample before it
+pub fn check_monotonic(curve: &[u16]) -> Result<(), usize> {
+    match curve.windows(2).position(|pair| pair[1] < pair[0]) {
+        Some(index) => Err(index + 1),
+        None => Ok(()),
+    }
+}
+
+/// Checks that a curve never increases, as with an intentionally inverted curve that goes from
+/// bright to dark. Returns the first index where the curve is higher than the sample before it,
+/// or 0 when the curve doesn't decrease at all and so isn't inverted
+pub fn check_descending(curve: &[u16]) -> Result<(), usize> {
+    if curve.first() <= curve.last() {
+        return Err(0);
+    }
+    match curve.windows(2).position(|pair| pair[1] > pair[0]) {
+        Some(index) => Err(index + 1),
+        None => Ok(()),
+    }
+}
+
+/// Finds the samples of a raw curve that are outside of the 0-1 range, which are clamped when
+/// the curve is parsed. Returns the index and value of each
+pub fn find_out_of_range(raw: &[f32]) -> Vec<(usize, f32)> {
+    raw.iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, value)| !(0.0..=1.0).contains(value))
+        .collect()
+}
+
+/// Finds the longest run of consecutive samples with the same value, where all input tones
+/// map to one output tone and so posterize. Returns the range of sample indices of the run,
+/// the first one among runs of the same length
+pub fn longest_plateau(curve: &[u16]) -> std::ops::Range<usize> {
+    let mut longest = 0..curve.len().min(1);
+    let mut start = 0;
+    for i in 1..=curve.len() {
+        if i == curve.len() || curve[i] != curve[start] {
+            if i - start > longest.len() {
+                longest = start..i;
+            }
+            start = i;
+        }
+    }
+    longest
+}
+
+/// Largest distance from the identity ramp, in 16-bit steps, at which a curve still counts
+/// as an identity curve
+pub const IDENTITY_TOLERANCE: u16 = 64;
+
+/// Checks whether a curve maps every value to itself within [IDENTITY_TOLERANCE], meaning
+/// it has no visible effect
+pub fn is_identity(curve: &[u16]) -> bool {
+    let last = curve.len().saturating_sub(1).max(1) as f64;
+    curve.iter().enumerate().all(|(i, &value)| {
+        let expected = (i as f64 / last * 65535.0).round() as u16;
+        value.abs_diff(expected) <= IDENTITY_TOLERANCE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramp_is_monotonic() {
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        assert_eq!(check_monotonic(&ramp), Ok(()));
+        // flat regions are allowed
+        assert_eq!(check_monotonic(&[0, 0, 100, 100, 65535]), Ok(()));
+    }
+
+    #[test]
+    fn dip_is_reported() {
+        assert_eq!(check_monotonic(&[0, 100, 200, 150, 300, 250]), Err(3));
+    }
+
+    #[test]
+    fn identity_is_detected() {
+        let ramp: Vec<u16> = (0..256).map(|x| x * 257).collect();
+        assert!(is_identity(&ramp));
+
+        let mut close = ramp.clone();
+        close[128] += IDENTITY_TOLERANCE;
+        assert!(is_identity(&close));
+
+        let gamma: Vec<u16> = (0..256)
+            .map(|x| ((x as f64 / 255.0).powf(2.2) * 65535.0) as u16)
+            .collect();
+        assert!(!is_identity(&gamma));
+    }
+
+    #[test]
+    fn out_of_range_samples_are_listed() {
+        assert_eq!(
+            find_out_of_range(&[0.0, -0.01, 0.5, 1.0, 1.2]),
+            vec![(1, -0.01), (4, 1.2)]
+        );
+        assert!(find_out_of_range(&[0.0, 1.0]).is_empty());
+    }
+
+    #[test]
+    fn clean_descending_ramp_is_accepted() {
+        let ramp: Vec<u16> = (0..256).rev().map(|x| x * 257).collect();
+        assert_eq!(check_descending(&ramp), Ok(()));
+        assert!(check_monotonic(&ramp).is_err());
+        // a flat curve is not an inversion
+        assert_eq!(check_descending(&[100, 100, 100]), Err(0));
+    }
+
+    #[test]
+    fn noisy_descending_ramp_is_reported() {
+        assert_eq!(check_descending(&[65535, 40000, 45000, 20000, 0]), Err(2));
+    }
+
+    #[test]
+    fn longest_plateau_is_found() {
+        assert_eq!(longest_plateau(&[0, 5, 5, 7, 9, 9, 9, 9]), 4..8);
+        assert_eq!(longest_plateau(&[0, 3, 3, 6, 6, 9]), 1..3);
+        assert_eq!(longest_plateau(&[1, 2, 3]), 0..1);
+        assert_eq!(longest_plateau(&[]), 0..0);
+    }
+}